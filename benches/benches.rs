@@ -2,14 +2,40 @@
 #![feature(test)]
 
 extern crate lando;
+extern crate serde;
 extern crate serde_json;
 extern crate test;
 
 use lando::GatewayRequest;
+use serde::Deserialize;
 use serde_json::Value;
 
 #[bench]
 fn gateway_conversion(b: &mut test::Bencher) {
     let event = serde_json::from_str::<Value>(include_str!("request.json")).unwrap();
-    b.iter(|| serde_json::from_value::<GatewayRequest>(event.clone()).unwrap());
+    b.iter(|| GatewayRequest::from_value(event.clone()).unwrap());
+}
+
+// events provided as json values, as they are by crowbar, borrowed rather than cloned
+#[bench]
+fn gateway_conversion_borrowed(b: &mut test::Bencher) {
+    let event = serde_json::from_str::<Value>(include_str!("request.json")).unwrap();
+    b.iter(|| GatewayRequest::deserialize(&event).unwrap());
+}
+
+// events provided as raw bytes, deserialized through an intermediate json value
+#[bench]
+fn gateway_conversion_via_value(b: &mut test::Bencher) {
+    let event = include_bytes!("request.json");
+    b.iter(|| {
+        let value = serde_json::from_slice::<Value>(event).unwrap();
+        GatewayRequest::deserialize(&value).map(|_| ()).unwrap()
+    });
+}
+
+// events provided as raw bytes, deserialized directly
+#[bench]
+fn gateway_conversion_from_slice(b: &mut test::Bencher) {
+    let event = include_bytes!("request.json");
+    b.iter(|| GatewayRequest::from_slice(event).unwrap());
 }
@@ -0,0 +1,182 @@
+//! parsing of `#[lando(...)]` arguments
+
+// third party
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, ExprArray, Ident, LitStr, Result};
+
+/// arguments accepted by the `lando` attribute
+#[derive(Default)]
+pub(crate) struct Args {
+    /// the name the handler is exported as
+    pub name: Option<LitStr>,
+    /// a `METHOD /path` route the handler serves
+    pub route: Option<LitStr>,
+    /// middleware applied around the handler, outermost first
+    pub middleware: Vec<Expr>,
+    /// a function initializing state shared across invocations
+    pub state: Option<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = Args::default();
+        let mut middleware = None;
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "name" {
+                let name: LitStr = input.parse()?;
+                check_name(&name)?;
+                set(&key, &mut args.name, name)?;
+            } else if key == "route" {
+                let route: LitStr = input.parse()?;
+                check_route(&route)?;
+                set(&key, &mut args.route, route)?;
+            } else if key == "middleware" {
+                let array: ExprArray = input.parse()?;
+                set(&key, &mut middleware, array.elems)?;
+            } else if key == "state" {
+                let init: Expr = input.parse()?;
+                set(&key, &mut args.state, init)?;
+            } else {
+                return Err(Error::new_spanned(
+                    key,
+                    "unsupported argument. expected name, route, middleware or state",
+                ));
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        if let (Some(name), Some(_)) = (&args.name, &args.route) {
+            return Err(Error::new_spanned(
+                name,
+                "routes are exported with the router serving them. use either name or route",
+            ));
+        }
+        args.middleware = middleware
+            .map(Punctuated::into_iter)
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        Ok(args)
+    }
+}
+
+fn set<T>(key: &Ident, slot: &mut Option<T>, value: T) -> Result<()> {
+    if slot.is_some() {
+        return Err(Error::new_spanned(
+            key,
+            format!("{} may only be provided once", key),
+        ));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// names are referenced as `lib{crate_name}.{name}` by lambda and must
+/// be valid python identifiers
+fn check_name(name: &LitStr) -> Result<()> {
+    let value = name.value();
+    let valid = value
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            name,
+            "name must start with a letter or underscore and contain only letters, digits and underscores",
+        ))
+    }
+}
+
+/// routes take the form `METHOD /path`, with parameters spanning whole segments
+fn check_route(route: &LitStr) -> Result<()> {
+    let value = route.value();
+    let parts = value.split_whitespace().collect::<Vec<_>>();
+    let valid = match parts.as_slice() {
+        [method, path] => {
+            method.chars().all(|c| c.is_ascii_alphabetic())
+                && path.starts_with('/')
+                && path.split('/').all(|segment| {
+                    !segment.contains(|c| c == '{' || c == '}')
+                        || (segment.starts_with('{')
+                            && segment.ends_with('}')
+                            && segment.len() > 2
+                            && !segment[1..segment.len() - 1].contains(|c| c == '{' || c == '}'))
+                })
+                && path
+                    .split('/')
+                    .filter(|segment| !segment.is_empty())
+                    .rev()
+                    .skip(1)
+                    .all(|segment| !segment.ends_with("+}"))
+        }
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            route,
+            "expected a route of the form METHOD /path, like GET /users/{id}",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_str;
+
+    fn error(args: &str) -> String {
+        match parse_str::<Args>(args) {
+            Ok(_) => panic!("expected {} to be rejected", args),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_args() {
+        let args = parse_str::<Args>("").expect("failed to parse");
+        assert!(args.name.is_none() && args.route.is_none() && args.middleware.is_empty());
+        let args =
+            parse_str::<Args>(r#"name = "api_handler", middleware = [cors, Limits::default()],"#)
+                .expect("failed to parse");
+        assert_eq!(
+            args.name.map(|name| name.value()),
+            Some("api_handler".into())
+        );
+        assert_eq!(args.middleware.len(), 2);
+        let args = parse_str::<Args>("state = db::connect").expect("failed to parse");
+        assert!(args.state.is_some());
+        let args = parse_str::<Args>(r#"route = "GET /users/{id}""#).expect("failed to parse");
+        assert_eq!(
+            args.route.map(|route| route.value()),
+            Some("GET /users/{id}".into())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_args() {
+        assert_eq!(
+            error(r#"path = "/""#),
+            "unsupported argument. expected name, route, middleware or state"
+        );
+        assert_eq!(
+            error(r#"name = "a", name = "b""#),
+            "name may only be provided once"
+        );
+        assert!(error(r#"name = "api-handler""#).starts_with("name must start with"));
+        assert!(error(r#"route = "/users""#).starts_with("expected a route"));
+        assert!(error(r#"route = "GET /users/{id""#).starts_with("expected a route"));
+        assert!(error(r#"route = "GET /{path+}/more""#).starts_with("expected a route"));
+        assert!(error(r#"name = "a", route = "GET /""#).starts_with("routes are exported"));
+        assert!(!error("middleware = cors").is_empty());
+        assert!(error("export = false").starts_with("unsupported argument"));
+    }
+}
@@ -1,30 +1,90 @@
 //! provides function attribute macros for AWS Api Gateway for use in lando
 
 extern crate proc_macro;
+extern crate proc_macro2;
 #[macro_use]
 extern crate quote;
+#[macro_use]
 extern crate syn;
 
+mod args;
+mod signature;
+mod validate;
+
 // std lib(ish)
 use proc_macro::TokenStream;
 
 // third party
-use syn::{parse, ItemFn, ReturnType};
+use syn::{parse, DeriveInput, Ident, Item};
+
+// ours
+use args::Args;
 
 /// Implements the `lando` attribute.
 ///
 /// This attribute is used to turn a Rust function into an AWS Gateway
-/// triggerable lambda. In lambda you can refer to these by path with
-/// `lib{crate_name}.{fn_name}`
+/// triggerable lambda. Attributed functions are exported by listing them in a single
+/// `export!` call, through which a crate's one Python module initializer is generated.
+/// In lambda you can refer to these by path with `lib{crate_name}.{fn_name}`
 ///
 /// # Examples
 ///
 /// ```rust,ignore
 /// #[macro_use] extern crate lando;
-/// use lando::{LambdaContext, Request, Response};
+/// use lando::{IntoResponse, LambdaContext, Request, Result};
+///
+/// #[lando]
+/// pub fn example(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("hello lambda")
+/// }
+///
+/// export!(example);
+/// ```
+///
+/// The attribute accepts optional arguments
+///
+/// * `name = "api_handler"` - exports the function under a different name
+/// * `middleware = [cors, logging]` - applies [middleware](https://softprops.github.io/lando/lando/middleware/index.html)
+///   around the function, outermost first
+/// * `route = "GET /users/{id}"` - rather than exporting the function, registers it as
+///   a route to be collected into a router with `routes!`
+/// * `state = init` - provides handlers and middleware with application state initialized
+///   once per cold start by `init`, available with `RequestExt#state`
+///
+/// ```rust,ignore
+/// #[lando]
+/// pub fn users(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("users")
+/// }
+///
+/// #[lando(name = "order_handler", middleware = [Limits::default()])]
+/// pub fn orders(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("orders")
+/// }
+///
+/// // exports libmycrate.users and libmycrate.order_handler
+/// export!(users, orders);
+/// ```
+///
+/// Attributed `async fn`s are run to completion on lando's executor
+///
+/// ```rust,ignore
+/// #[lando]
+/// pub async fn example(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("hello lambda")
+/// }
+///
+/// export!(example);
+/// ```
+///
+/// Functions which are generic over types or take `self`, or whose
+/// arguments and return type differ from the above, are rejected with a
+/// compile error pointing at the offending tokens.
 ///
+/// ```rust,ignore
 /// #[lando]
-/// pub fn example<'a>(_: Request, _: LambdaContext) -> Response<&'a str> {
+/// pub fn example(_: Request) -> Result<impl IntoResponse> {
+/// //            ^^^^^^^^^^ the 'lando' attribute requires a function with two arguments
 ///   Ok("hello lambda")
 /// }
 /// ```
@@ -33,42 +93,120 @@ pub fn lando(args: TokenStream, input: TokenStream) -> TokenStream {
     attr_impl(args, input)
 }
 
-// implementation. should expect the following
-// * verify function type
-// * input args are (lando::Request, lando::LambdaContext)
-// * return type is lando::LandoResponse
-fn attr_impl(_: TokenStream, input: TokenStream) -> TokenStream {
-    let target: ItemFn = match parse(input.clone()) {
-        Ok(f) => f,
-        _ => {
-            panic!("the 'lando' attribute can only be used on functions");
-            // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-            // use the following when this becomes stable
-            /*Span::call_site()
-            .error("the 'lando' attribute can only be used on functions")
-            .emit();*/        }
+/// Implements the `Validate` derive.
+///
+/// Fields are validated according to their `#[validate(...)]` attributes.
+/// See the [validate module](https://softprops.github.io/lando/lando/validate/index.html)
+/// for the supported constraints.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// #[macro_use] extern crate lando;
+/// #[macro_use] extern crate serde_derive;
+///
+/// #[derive(Deserialize, Validate)]
+/// pub struct Signup {
+///   #[validate(email)]
+///   email: String,
+///   #[validate(length(min = 1, max = 32))]
+///   name: String,
+/// }
+/// ```
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn validate(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = match parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error().into(),
     };
-    if target.decl.inputs.len() != 2 {
-        panic!(
-            "the 'lando' attribute requires a function with two arguments. expecting {}(_: lando::Request, _: lando::LambdaContext) -> lando::Result", target.ident
-            );
-        // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-        // use the following when it becomes stable
-    }
-    match target.decl.output {
-        ReturnType::Default => {
-            // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-            // use the following when it becomes stable
-            panic!("the 'lando' attribute requires a function that returns a value. expecting {}(_: lando::Request, _: lando::LambdaContext) -> lando::Result", target.ident);
-        }
-        _ => (),
+    match validate::derive(input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
     }
+}
+
+// implementation. verifies the attributed item is a function accepting
+// (lando::Request, lando::LambdaContext) and returning lando::Result,
+// reporting problems as compile errors spanning the offending tokens
+fn attr_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+    let (args, target) = match parse::<Args>(args).and_then(|args| {
+        parse::<Item>(input)
+            .and_then(signature::handler)
+            .map(|target| (args, target))
+    }) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
     let target_ident = target.ident.clone();
-    let target_name = target_ident.to_string();
-    let expanded = quote! {
-        #target
+    // middleware is applied outermost first, within any state
+    // async functions are driven to completion by lando's executor
+    let inner = match target.asyncness {
+        Some(_) => quote!(::lando::executor::blocking(#target_ident)),
+        _ => quote!(#target_ident),
+    };
+    let handler = args.middleware.iter().rev().fold(
+        inner,
+        |inner, middleware| quote!(::lando::middleware::wrap(#middleware, #inner)),
+    );
+    let handler = match args.state {
+        Some(init) => {
+            quote!(::lando::middleware::wrap(::lando::state::State::new(#init), #handler))
+        }
+        _ => handler,
+    };
+    let expanded = match args.route {
+        Some(route) => {
+            let vis = &target.vis;
+            let route_ident = Ident::new(
+                &format!("__lando_route_{}", target_ident),
+                target_ident.span(),
+            );
+            quote! {
+                #target
 
-        gateway!(#target_name => #target_ident);
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                #vis fn #route_ident() -> ::lando::router::Route {
+                    ::lando::router::Route::new(#route, |request, context| {
+                        (#handler)(request, context)
+                    })
+                    .expect("invalid route")
+                }
+            }
+        }
+        _ => {
+            let vis = &target.vis;
+            let target_name = args
+                .name
+                .map(|name| name.value())
+                .unwrap_or_else(|| target_ident.to_string());
+            let name_ident = Ident::new(
+                &format!("__lando_name_{}", target_ident),
+                target_ident.span(),
+            );
+            let handler_ident = Ident::new(
+                &format!("__lando_handler_{}", target_ident),
+                target_ident.span(),
+            );
+            // a crate may define only one module initializer, which lando::export!
+            // generates for all of its handlers
+            quote! {
+                #target
+
+                #[doc(hidden)]
+                #[allow(non_upper_case_globals)]
+                #vis const #name_ident: &str = #target_name;
+
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                #vis fn #handler_ident(
+                    request: ::lando::Request,
+                    context: ::lando::LambdaContext,
+                ) -> ::lando::Result<::lando::Response<::lando::Body>> {
+                    (#handler)(request, context).map(::lando::IntoResponse::into_response)
+                }
+            }
+        }
     };
     expanded.into()
 }
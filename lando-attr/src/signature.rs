@@ -0,0 +1,208 @@
+//! validation of `#[lando]` handler signatures
+
+// third party
+use syn::{
+    Error, FnArg, GenericArgument, GenericParam, Item, ItemFn, PathArguments, Result, ReturnType,
+    Type, TypeParamBound,
+};
+
+const EXPECTED: &str =
+    "expecting fn(lando::Request, lando::LambdaContext) -> lando::Result<impl lando::IntoResponse>";
+
+/// parse the item the attribute was applied to, returning the handler
+/// function if its signature is supported
+pub(crate) fn handler(item: Item) -> Result<ItemFn> {
+    let target = match item {
+        Item::Fn(target) => target,
+        other => {
+            return Err(Error::new_spanned(
+                other,
+                "the 'lando' attribute can only be used on functions",
+            ))
+        }
+    };
+    check(&target)?;
+    Ok(target)
+}
+
+fn check(target: &ItemFn) -> Result<()> {
+    for param in &target.decl.generics.params {
+        match param {
+            GenericParam::Lifetime(_) => (),
+            other => {
+                return Err(Error::new_spanned(
+                    other,
+                    "the 'lando' attribute does not support generic functions. only lifetime parameters are allowed",
+                ))
+            }
+        }
+    }
+    let inputs = &target.decl.inputs;
+    for input in inputs {
+        match input {
+            FnArg::SelfRef(_) | FnArg::SelfValue(_) => return Err(Error::new_spanned(
+                input,
+                "the 'lando' attribute can not be used on methods. declare a free function instead",
+            )),
+            _ => (),
+        }
+    }
+    if inputs.len() != 2 {
+        let message = format!(
+            "the 'lando' attribute requires a function with two arguments. {}",
+            EXPECTED
+        );
+        return Err(if inputs.is_empty() {
+            Error::new(target.ident.span(), message)
+        } else {
+            Error::new_spanned(inputs, message)
+        });
+    }
+    check_arg(&inputs[0], "Request")?;
+    check_arg(&inputs[1], "LambdaContext")?;
+    match target.decl.output {
+        ReturnType::Default => Err(Error::new_spanned(
+            &target.ident,
+            format!(
+                "the 'lando' attribute requires a function that returns a value. {}",
+                EXPECTED
+            ),
+        )),
+        ReturnType::Type(_, ref ty) => check_output(ty),
+    }
+}
+
+/// arguments are expected to be typed as a path ending in `expected`
+fn check_arg(arg: &FnArg, expected: &str) -> Result<()> {
+    let ty = match arg {
+        FnArg::Captured(captured) => &captured.ty,
+        FnArg::Ignored(ty) => ty,
+        other => {
+            return Err(Error::new_spanned(
+                other,
+                format!("expected an argument of type lando::{}", expected),
+            ))
+        }
+    };
+    if last_segment(ty) == Some(expected.to_string()) {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            ty,
+            format!("expected lando::{}. {}", expected, EXPECTED),
+        ))
+    }
+}
+
+/// the return type is expected to be a `Result` whose success type
+/// is concrete or `impl IntoResponse`
+fn check_output(ty: &Type) -> Result<()> {
+    let invalid = || Error::new_spanned(ty, format!("expected lando::Result. {}", EXPECTED));
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last().map(|pair| pair.into_value()),
+        _ => None,
+    };
+    let segment = match segment {
+        Some(segment) if segment.ident == "Result" => segment,
+        _ => return Err(invalid()),
+    };
+    let success = match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => {
+            match args.args.first().map(|p| p.into_value()) {
+                Some(GenericArgument::Type(ty)) => ty,
+                _ => return Err(invalid()),
+            }
+        }
+        _ => return Err(invalid()),
+    };
+    if let Type::ImplTrait(ref bounds) = success {
+        let into_response = bounds.bounds.iter().any(|bound| match bound {
+            TypeParamBound::Trait(bound) => bound
+                .path
+                .segments
+                .last()
+                .map_or(false, |pair| pair.value().ident == "IntoResponse"),
+            _ => false,
+        });
+        if !into_response {
+            return Err(Error::new_spanned(
+                success,
+                "expected impl lando::IntoResponse",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn last_segment(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .filter(|pair| pair.value().arguments.is_empty())
+            .map(|pair| pair.value().ident.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_str;
+
+    fn error(item: &str) -> String {
+        match handler(parse_str(item).expect("invalid item")) {
+            Ok(_) => panic!("expected {} to be rejected", item),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn accepts_handlers() {
+        for item in &[
+            "fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> { Ok(()) }",
+            "async fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> { Ok(()) }",
+            "pub fn handler<'a>(req: lando::Request, ctx: lando::LambdaContext) -> lando::Result<lando::Response<&'a str>> { Ok(()) }",
+            "fn handler(_: Request, _: LambdaContext) -> ::std::result::Result<impl IntoResponse + Send, Box<::std::error::Error>> { Ok(()) }",
+        ] {
+            assert!(handler(parse_str(item).unwrap()).is_ok(), "rejected {}", item);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_handlers() {
+        assert_eq!(
+            error("struct Handler;"),
+            "the 'lando' attribute can only be used on functions"
+        );
+        assert!(
+            error("fn handler<T>(_: Request, _: LambdaContext) -> Result<T> { Ok(()) }")
+                .contains("does not support generic functions")
+        );
+        assert!(
+            error("fn handler(&self, _: Request, _: LambdaContext) -> Result<()> { Ok(()) }")
+                .contains("can not be used on methods")
+        );
+        assert!(error("fn handler(_: Request) -> Result<()> { Ok(()) }")
+            .contains("requires a function with two arguments"));
+        assert!(
+            error("fn handler(_: String, _: LambdaContext) -> Result<()> { Ok(()) }")
+                .starts_with("expected lando::Request")
+        );
+        assert!(
+            error("fn handler(_: Request, _: Request) -> Result<()> { Ok(()) }")
+                .starts_with("expected lando::LambdaContext")
+        );
+        assert!(error("fn handler(_: Request, _: LambdaContext) {}")
+            .contains("requires a function that returns a value"));
+        assert!(
+            error("fn handler(_: Request, _: LambdaContext) -> String { }")
+                .starts_with("expected lando::Result")
+        );
+        assert_eq!(
+            error("fn handler(_: Request, _: LambdaContext) -> Result<impl Clone> { Ok(()) }"),
+            "expected impl lando::IntoResponse"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! implementation of `#[derive(Validate)]`
+
+// third party
+use proc_macro2::{Literal, TokenStream};
+use syn::{
+    Attribute, Data, DeriveInput, Error, Field, Fields, GenericArgument, Lit, Meta, NestedMeta,
+    Path, PathArguments, Result, Type,
+};
+
+/// a single field constraint declared with `#[validate(...)]`
+enum Constraint {
+    Length(Option<usize>, Option<usize>),
+    Range(Option<f64>, Option<f64>),
+    Email,
+    Nested,
+    Custom(Path),
+}
+
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Validate can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Validate can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let rename_all = serde_value(&input.attrs, "rename_all")?;
+    let mut checks = Vec::new();
+    for field in fields {
+        checks.push(field_checks(
+            field,
+            rename_all.as_ref().map(String::as_str),
+        )?);
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lando::Validate for #ident #ty_generics #where_clause {
+            fn validate(&self) -> ::std::result::Result<(), ::lando::ValidationErrors> {
+                let mut errors = ::lando::ValidationErrors::new();
+                #(#checks)*
+                errors.into_result()
+            }
+        }
+    })
+}
+
+fn field_checks(field: &Field, rename_all: Option<&str>) -> Result<TokenStream> {
+    let ident = match field.ident {
+        Some(ref ident) => ident,
+        _ => return Ok(TokenStream::new()),
+    };
+    let mut constraints = Vec::new();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("validate"))
+    {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested.iter() {
+                    constraints.push(constraint(nested)?);
+                }
+            }
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "expected #[validate(...)] with a list of constraints",
+                ))
+            }
+        }
+    }
+    if constraints.is_empty() {
+        return Ok(TokenStream::new());
+    }
+    let path = match serde_value(&field.attrs, "rename")? {
+        Some(name) => name,
+        _ => rename(
+            ident.to_string().trim_start_matches("r#"),
+            rename_all.unwrap_or_default(),
+        ),
+    };
+    let checks = constraints.iter().map(|constraint| match constraint {
+        Constraint::Length(min, max) => {
+            let min = optional(min.map(Literal::usize_suffixed));
+            let max = optional(max.map(Literal::usize_suffixed));
+            quote! { ::lando::validate::length(&mut errors, #path, value, #min, #max); }
+        }
+        Constraint::Range(min, max) => {
+            let min = optional(min.map(Literal::f64_suffixed));
+            let max = optional(max.map(Literal::f64_suffixed));
+            quote! { ::lando::validate::range(&mut errors, #path, value, #min, #max); }
+        }
+        Constraint::Email => quote! { ::lando::validate::email(&mut errors, #path, value); },
+        Constraint::Nested => quote! { ::lando::validate::nested(&mut errors, #path, value); },
+        Constraint::Custom(function) => {
+            quote! { ::lando::validate::custom(&mut errors, #path, value, #function); }
+        }
+    });
+    Ok(if is_option(&field.ty) {
+        quote! {
+            if let Some(ref value) = self.#ident {
+                #(#checks)*
+            }
+        }
+    } else {
+        quote! {
+            {
+                let value = &self.#ident;
+                #(#checks)*
+            }
+        }
+    })
+}
+
+fn optional(value: Option<Literal>) -> TokenStream {
+    match value {
+        Some(value) => quote! { Some(#value) },
+        _ => quote! { None },
+    }
+}
+
+fn constraint(nested: &NestedMeta) -> Result<Constraint> {
+    match nested {
+        NestedMeta::Meta(Meta::Word(ref ident)) if ident == "email" => Ok(Constraint::Email),
+        NestedMeta::Meta(Meta::Word(ref ident)) if ident == "nested" => Ok(Constraint::Nested),
+        NestedMeta::Meta(Meta::List(ref list)) if list.ident == "length" => {
+            let (min, max) = bounds(list.nested.iter())?;
+            let min = integer(min)?;
+            let max = integer(max)?;
+            if min.is_none() && max.is_none() {
+                return Err(Error::new_spanned(list, "length requires a min or max"));
+            }
+            Ok(Constraint::Length(min, max))
+        }
+        NestedMeta::Meta(Meta::List(ref list)) if list.ident == "range" => {
+            let (min, max) = bounds(list.nested.iter())?;
+            let min = float(min)?;
+            let max = float(max)?;
+            if min.is_none() && max.is_none() {
+                return Err(Error::new_spanned(list, "range requires a min or max"));
+            }
+            Ok(Constraint::Range(min, max))
+        }
+        NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.ident == "custom" => match pair.lit {
+            Lit::Str(ref function) => Ok(Constraint::Custom(function.parse()?)),
+            ref lit => Err(Error::new_spanned(
+                lit,
+                "expected a function path as a string, i.e. custom = \"validate_name\"",
+            )),
+        },
+        other => Err(Error::new_spanned(
+            other,
+            "unknown constraint. expected one of length, range, email, nested or custom",
+        )),
+    }
+}
+
+fn bounds<'a, I>(nested: I) -> Result<(Option<&'a Lit>, Option<&'a Lit>)>
+where
+    I: Iterator<Item = &'a NestedMeta>,
+{
+    let (mut min, mut max) = (None, None);
+    for meta in nested {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.ident == "min" => {
+                min = Some(&pair.lit)
+            }
+            NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.ident == "max" => {
+                max = Some(&pair.lit)
+            }
+            other => return Err(Error::new_spanned(other, "expected min = ... or max = ...")),
+        }
+    }
+    Ok((min, max))
+}
+
+fn integer(lit: Option<&Lit>) -> Result<Option<usize>> {
+    match lit {
+        None => Ok(None),
+        Some(Lit::Int(ref int)) => Ok(Some(int.value() as usize)),
+        Some(other) => Err(Error::new_spanned(other, "expected an integer")),
+    }
+}
+
+fn float(lit: Option<&Lit>) -> Result<Option<f64>> {
+    match lit {
+        None => Ok(None),
+        Some(Lit::Int(ref int)) => Ok(Some(int.value() as f64)),
+        Some(Lit::Float(ref float)) => Ok(Some(float.value())),
+        // negative numbers are not literals in attributes, so accept them as strings
+        Some(Lit::Str(ref string)) => string
+            .value()
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| Error::new_spanned(string, "expected a number")),
+        Some(other) => Err(Error::new_spanned(other, "expected a number")),
+    }
+}
+
+/// returns the value of a `#[serde(name = "value")]` attribute, if present
+fn serde_value(attrs: &[Attribute], name: &str) -> Result<Option<String>> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+        // serde's own derive reports malformed serde attributes
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested.iter() {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.ident == name => {
+                        if let Lit::Str(ref value) = pair.lit {
+                            return Ok(Some(value.value()));
+                        }
+                    }
+                    NestedMeta::Meta(Meta::List(ref list)) if list.ident == name => {
+                        // i.e. rename(deserialize = "...")
+                        for nested in list.nested.iter() {
+                            if let NestedMeta::Meta(Meta::NameValue(ref pair)) = nested {
+                                if pair.ident == "deserialize" {
+                                    if let Lit::Str(ref value) = pair.lit {
+                                        return Ok(Some(value.value()));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// applies a serde `rename_all` rule to a snake case field name
+fn rename(field: &str, rule: &str) -> String {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            _ => String::new(),
+        }
+    };
+    match rule {
+        "lowercase" => field.to_lowercase(),
+        "UPPERCASE" => field.to_uppercase(),
+        "PascalCase" => field.split('_').map(capitalize).collect(),
+        "camelCase" => {
+            let pascal = field.split('_').map(capitalize).collect::<String>();
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                _ => pascal,
+            }
+        }
+        "SCREAMING_SNAKE_CASE" => field.to_uppercase(),
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.to_uppercase().replace('_', "-"),
+        _ => field.to_string(),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ref path) if path.qself.is_none() => path
+            .path
+            .segments
+            .iter()
+            .last()
+            .map(|segment| {
+                segment.ident == "Option"
+                    && match segment.arguments {
+                        PathArguments::AngleBracketed(ref args) => {
+                            args.args.len() == 1
+                                && match args.args[0] {
+                                    GenericArgument::Type(_) => true,
+                                    _ => false,
+                                }
+                        }
+                        _ => false,
+                    }
+            })
+            .unwrap_or_default(),
+        _ => false,
+    }
+}
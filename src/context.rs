@@ -0,0 +1,249 @@
+//! Lambda execution context
+
+// Std
+use std::time::{Duration, Instant};
+
+// Third Party
+use crowbar;
+
+/// Information about a Lambda function invocation and its execution environment
+///
+/// Handlers receive a context for each invocation. Contexts may also be constructed
+/// for testing handlers outside of Lambda. The default context describes a
+/// function named `test` with 3 seconds remaining.
+///
+/// ```rust
+/// # extern crate lando;
+/// use lando::LambdaContext;
+/// use std::time::Duration;
+///
+/// let context = LambdaContext::default()
+///   .with_function_name("orders")
+///   .with_aws_request_id("abc-123")
+///   .with_remaining_time(Duration::from_millis(500));
+/// assert_eq!(context.function_name(), "orders");
+/// assert!(context.get_remaining_time_in_millis() <= 500);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LambdaContext {
+    function_name: String,
+    function_version: String,
+    invoked_function_arn: String,
+    memory_limit_in_mb: i32,
+    aws_request_id: String,
+    log_group_name: String,
+    log_stream_name: String,
+    identity: Option<CognitoIdentity>,
+    deadline: Instant,
+}
+
+/// Amazon Cognito identity of the caller, for invocations through the AWS mobile SDKs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CognitoIdentity {
+    /// The authenticated Cognito identity
+    pub identity_id: String,
+    /// The Cognito identity pool which authorized the invocation
+    pub identity_pool_id: String,
+}
+
+impl Default for LambdaContext {
+    fn default() -> Self {
+        LambdaContext {
+            function_name: "test".into(),
+            function_version: "$LATEST".into(),
+            invoked_function_arn: "arn:aws:lambda:us-east-1:123456789012:function:test".into(),
+            memory_limit_in_mb: 128,
+            aws_request_id: String::new(),
+            log_group_name: "/aws/lambda/test".into(),
+            log_stream_name: String::new(),
+            identity: None,
+            deadline: Instant::now() + Duration::from_secs(3),
+        }
+    }
+}
+
+impl LambdaContext {
+    /// Return the name of the Lambda function
+    pub fn function_name(&self) -> &str {
+        &self.function_name
+    }
+
+    /// Return the version of the function being executed
+    pub fn function_version(&self) -> &str {
+        &self.function_version
+    }
+
+    /// Return the ARN used to invoke the function, which may include a version or alias
+    pub fn invoked_function_arn(&self) -> &str {
+        &self.invoked_function_arn
+    }
+
+    /// Return the amount of memory configured for the function
+    pub fn memory_limit_in_mb(&self) -> i32 {
+        self.memory_limit_in_mb
+    }
+
+    /// Return the identifier of the invocation request
+    pub fn aws_request_id(&self) -> &str {
+        &self.aws_request_id
+    }
+
+    /// Return the CloudWatch log group of the function
+    pub fn log_group_name(&self) -> &str {
+        &self.log_group_name
+    }
+
+    /// Return the CloudWatch log stream of the function instance
+    pub fn log_stream_name(&self) -> &str {
+        &self.log_stream_name
+    }
+
+    /// Return the Cognito identity of the caller, when available
+    pub fn identity(&self) -> Option<&CognitoIdentity> {
+        self.identity.as_ref()
+    }
+
+    /// Return the instant at which the invocation will time out
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Return the number of milliseconds left before the invocation times out
+    pub fn get_remaining_time_in_millis(&self) -> u64 {
+        let remaining = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .unwrap_or_default();
+        remaining.as_secs() * 1000 + u64::from(remaining.subsec_millis())
+    }
+
+    /// Set the name of the Lambda function
+    pub fn with_function_name<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.function_name = name.into();
+        self
+    }
+
+    /// Set the version of the function being executed
+    pub fn with_function_version<V>(mut self, version: V) -> Self
+    where
+        V: Into<String>,
+    {
+        self.function_version = version.into();
+        self
+    }
+
+    /// Set the ARN used to invoke the function
+    pub fn with_invoked_function_arn<A>(mut self, arn: A) -> Self
+    where
+        A: Into<String>,
+    {
+        self.invoked_function_arn = arn.into();
+        self
+    }
+
+    /// Set the amount of memory configured for the function
+    pub fn with_memory_limit_in_mb(mut self, memory: i32) -> Self {
+        self.memory_limit_in_mb = memory;
+        self
+    }
+
+    /// Set the identifier of the invocation request
+    pub fn with_aws_request_id<I>(mut self, id: I) -> Self
+    where
+        I: Into<String>,
+    {
+        self.aws_request_id = id.into();
+        self
+    }
+
+    /// Set the CloudWatch log group of the function
+    pub fn with_log_group_name<G>(mut self, group: G) -> Self
+    where
+        G: Into<String>,
+    {
+        self.log_group_name = group.into();
+        self
+    }
+
+    /// Set the CloudWatch log stream of the function instance
+    pub fn with_log_stream_name<S>(mut self, stream: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.log_stream_name = stream.into();
+        self
+    }
+
+    /// Set the Cognito identity of the caller
+    pub fn with_identity(mut self, identity: CognitoIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Set the time left, from now, before the invocation times out
+    pub fn with_remaining_time(self, remaining: Duration) -> Self {
+        self.with_deadline(Instant::now() + remaining)
+    }
+
+    /// Set the instant at which the invocation times out
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = deadline;
+        self
+    }
+}
+
+impl<'a> From<&'a crowbar::LambdaContext<'a>> for LambdaContext {
+    fn from(context: &'a crowbar::LambdaContext<'a>) -> Self {
+        // the remaining time is resolved once, while we hold python's gil,
+        // and tracked as a deadline thereafter
+        let remaining = context.get_remaining_time_in_millis().unwrap_or_default();
+        LambdaContext {
+            function_name: context.function_name().into(),
+            function_version: context.function_version().into(),
+            invoked_function_arn: context.invoked_function_arn().into(),
+            memory_limit_in_mb: context.memory_limit_in_mb().parse().unwrap_or_default(),
+            aws_request_id: context.aws_request_id().into(),
+            log_group_name: context.log_group_name().into(),
+            log_stream_name: context.log_stream_name().into(),
+            // crowbar does not surface cognito identities
+            identity: None,
+            deadline: Instant::now() + Duration::from_millis(remaining),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_time_counts_down_to_zero() {
+        let context = LambdaContext::default().with_remaining_time(Duration::from_secs(1));
+        let remaining = context.get_remaining_time_in_millis();
+        assert!(remaining > 0 && remaining <= 1000);
+        let context = context.with_deadline(Instant::now() - Duration::from_millis(1));
+        assert_eq!(context.get_remaining_time_in_millis(), 0);
+    }
+
+    #[test]
+    fn contexts_are_constructible() {
+        let context = LambdaContext::default()
+            .with_function_name("orders")
+            .with_function_version("2")
+            .with_memory_limit_in_mb(512)
+            .with_identity(CognitoIdentity {
+                identity_id: "id".into(),
+                identity_pool_id: "pool".into(),
+            });
+        assert_eq!(context.function_name(), "orders");
+        assert_eq!(context.function_version(), "2");
+        assert_eq!(context.memory_limit_in_mb(), 512);
+        assert_eq!(
+            context.identity().map(|i| i.identity_id.as_str()),
+            Some("id")
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Run asynchronous handlers
+//!
+//! Handlers are invoked synchronously. Asynchronous handlers, `async fn`s or closures
+//! returning futures, are adapted to synchronous ones with [blocking](fn.blocking.html),
+//! which drives their futures to completion on an executor owned by lando. The executor
+//! parks the invoking thread while futures wait and is reused across invocations.
+//!
+//! ```rust,ignore
+//! #[macro_use] extern crate lando;
+//! use lando::executor::blocking;
+//! use lando::{IntoResponse, LambdaContext, Request, Result};
+//!
+//! async fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   Ok("👋")
+//! }
+//!
+//! gateway!(blocking(handler));
+//! ```
+//!
+//! [gateway!](../macro.gateway.html) requires this adapter, as it does not accept asynchronous
+//! handlers directly. `#[lando]` applies it to attributed `async fn`s for you and
+//! [runtime::run_async](../runtime/fn.run_async.html) to handlers served natively. Futures
+//! still pending when their request's deadline passes are dropped.
+//!
+//! By default the executor does not provide an IO reactor or timers. With the `tokio` feature
+//! enabled, futures are instead driven by a single threaded tokio runtime, also reused across
+//! invocations, so handlers may await tokio based clients, sockets and timers.
+//!
+//! ```toml
+//! [dependencies]
+//! lando = { version = "0.2", features = ["tokio"] }
+//! ```
+//!
+//! Futures blocked on from within another future on the same thread are driven without the
+//! runtime's reactor, as it is already driving the outer future.
+
+// Std
+use std::cell::RefCell;
+use std::future::Future;
+use std::mem;
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+// Third Party
+#[cfg(feature = "tokio")]
+use tokio::runtime::{Builder, Handle, Runtime};
+
+// Ours
+use ext::RequestExt;
+use timeout::DeadlineExceeded;
+use {LambdaContext, Request, Result};
+
+/// Wakes a thread parked waiting on a future
+struct Signal {
+    thread: Thread,
+    notified: AtomicBool,
+}
+
+impl Signal {
+    fn notify(&self) {
+        self.notified.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+
+    /// Park until notified or the deadline passes, tolerating spurious wakeups.
+    /// Return false if the deadline passed
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        while !self.notified.swap(false, Ordering::SeqCst) {
+            match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if timeout > Duration::from_secs(0) => {
+                        thread::park_timeout(timeout)
+                    }
+                    _ => return false,
+                },
+                _ => thread::park(),
+            }
+        }
+        true
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+
+unsafe fn clone(signal: *const ()) -> RawWaker {
+    let signal = Arc::from_raw(signal as *const Signal);
+    mem::forget(signal.clone());
+    RawWaker::new(Arc::into_raw(signal) as *const (), &VTABLE)
+}
+
+unsafe fn wake(signal: *const ()) {
+    Arc::from_raw(signal as *const Signal).notify();
+}
+
+unsafe fn wake_by_ref(signal: *const ()) {
+    (*(signal as *const Signal)).notify();
+}
+
+unsafe fn drop(signal: *const ()) {
+    mem::drop(Arc::from_raw(signal as *const Signal));
+}
+
+/// A single threaded executor
+struct Executor {
+    signal: Arc<Signal>,
+    waker: Waker,
+}
+
+impl Executor {
+    fn new() -> Self {
+        let signal = Arc::new(Signal {
+            thread: thread::current(),
+            notified: AtomicBool::new(false),
+        });
+        let raw = RawWaker::new(Arc::into_raw(signal.clone()) as *const (), &VTABLE);
+        Executor {
+            signal,
+            waker: unsafe { Waker::from_raw(raw) },
+        }
+    }
+
+    fn block_on<F>(
+        &self,
+        future: F,
+        deadline: Option<Instant>,
+    ) -> StdResult<F::Output, DeadlineExceeded>
+    where
+        F: Future,
+    {
+        let mut future = Box::pin(future);
+        let mut context = Context::from_waker(&self.waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return Ok(output);
+            }
+            if !self.signal.wait(deadline) {
+                return Err(DeadlineExceeded);
+            }
+        }
+    }
+}
+
+/// Run a future to completion on a runtime providing tokio's reactor and timers
+#[cfg(feature = "tokio")]
+fn block_on_runtime<F>(
+    runtime: &Runtime,
+    future: F,
+    deadline: Option<Instant>,
+) -> StdResult<F::Output, DeadlineExceeded>
+where
+    F: Future,
+{
+    match deadline {
+        Some(deadline) => {
+            // timeouts register with the runtime's timer when created
+            let _context = runtime.enter();
+            let future = tokio::time::timeout_at(deadline.into(), future);
+            runtime.block_on(future).map_err(|_| DeadlineExceeded)
+        }
+        _ => Ok(runtime.block_on(future)),
+    }
+}
+
+thread_local! {
+    // Lambda invokes handlers on the same thread, so the executor outlives invocations
+    static EXECUTOR: RefCell<Option<Executor>> = RefCell::new(None);
+}
+
+#[cfg(feature = "tokio")]
+thread_local! {
+    static RUNTIME: Runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build tokio runtime");
+}
+
+/// Run a future to completion on the current thread, returning its output
+///
+/// Futures awaiting other work park the thread until they are woken. With the `tokio` feature
+/// the thread instead drives tokio's reactor and timers while futures are pending.
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future,
+{
+    run(future, None)
+        .unwrap_or_else(|_| unreachable!("futures without deadlines run to completion"))
+}
+
+/// Run a future to completion on the current thread, returning its output, unless `deadline`
+/// passes first. The future is then dropped, cancelling its work
+pub fn block_on_until<F>(future: F, deadline: Instant) -> StdResult<F::Output, DeadlineExceeded>
+where
+    F: Future,
+{
+    run(future, Some(deadline))
+}
+
+fn run<F>(future: F, deadline: Option<Instant>) -> StdResult<F::Output, DeadlineExceeded>
+where
+    F: Future,
+{
+    // a runtime can not block within itself, nested futures fall back to our executor
+    #[cfg(feature = "tokio")]
+    {
+        if Handle::try_current().is_err() {
+            return RUNTIME.with(|runtime| block_on_runtime(runtime, future, deadline));
+        }
+    }
+    EXECUTOR.with(|executor| match executor.try_borrow_mut() {
+        Ok(mut executor) => executor
+            .get_or_insert_with(Executor::new)
+            .block_on(future, deadline),
+        // block_on was called from within a future on this thread
+        _ => Executor::new().block_on(future, deadline),
+    })
+}
+
+/// Return a synchronous handler which runs an asynchronous handler's
+/// future to completion with [block_on](fn.block_on.html)
+///
+/// Handlers are cancelled when their request's [deadline](../trait.RequestExt.html#tymethod.deadline)
+/// passes, failing with [DeadlineExceeded](../struct.DeadlineExceeded.html).
+pub fn blocking<F, Fut, R>(handler: F) -> impl FnOnce(Request, LambdaContext) -> Result<R>
+where
+    F: FnOnce(Request, LambdaContext) -> Fut,
+    Fut: Future<Output = Result<R>>,
+{
+    move |request, context| match request.deadline() {
+        Some(deadline) => block_on_until(handler(request, context), deadline)?,
+        _ => block_on(handler(request, context)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use Body;
+
+    /// A future completed by another thread
+    struct Delayed {
+        value: Arc<Mutex<Option<&'static str>>>,
+        spawned: bool,
+    }
+
+    impl Future for Delayed {
+        type Output = Result<&'static str>;
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+            if let Some(value) = *self.value.lock().unwrap() {
+                return Poll::Ready(Ok(value));
+            }
+            if !self.spawned {
+                self.spawned = true;
+                let (value, waker) = (self.value.clone(), context.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    *value.lock().unwrap() = Some("done");
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    fn delayed() -> Delayed {
+        Delayed {
+            value: Arc::new(Mutex::new(None)),
+            spawned: false,
+        }
+    }
+
+    #[test]
+    fn block_on_waits_for_wakeups() {
+        // the executor is reused for each future
+        for _ in 0..3 {
+            assert_eq!(block_on(delayed()).expect("future failed"), "done");
+        }
+    }
+
+    #[test]
+    fn block_on_may_be_nested() {
+        struct Nested;
+
+        impl Future for Nested {
+            type Output = &'static str;
+
+            fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+                Poll::Ready(block_on(delayed()).expect("future failed"))
+            }
+        }
+
+        assert_eq!(block_on(Nested), "done");
+    }
+
+    #[test]
+    fn block_on_until_cancels_futures() {
+        let deadline = Instant::now() + Duration::from_millis(1);
+        assert_eq!(
+            block_on_until(delayed(), deadline).err(),
+            Some(DeadlineExceeded)
+        );
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(block_on_until(delayed(), deadline).is_ok());
+    }
+
+    #[test]
+    fn blocking_adapts_asynchronous_handlers() {
+        let handler = blocking(|_, _| delayed());
+        assert_eq!(
+            handler(Request::new(Body::Empty), LambdaContext::default()).expect("handler failed"),
+            "done"
+        );
+    }
+}
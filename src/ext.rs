@@ -1,16 +1,37 @@
 //! Extension methods for `http::Request` types
 
+// Std
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 // Third Party
 use http::header::CONTENT_TYPE;
 use http::Request as HttpRequest;
+#[cfg(feature = "msgpack")]
+use rmp_serde;
 use serde::de::value::Error as SerdeError;
 use serde::Deserialize;
+#[cfg(feature = "cbor")]
+use serde_cbor;
 use serde_json;
+use serde_path_to_error::{self, Path, Segment};
 use serde_urlencoded;
+#[cfg(feature = "xml")]
+use serde_xml_rs;
+#[cfg(feature = "yaml")]
+use serde_yaml;
+use url::form_urlencoded;
 
 // Ours
+use form::{FormDecoding, Node};
+use limits::Limits;
+use negotiate;
 use request::RequestContext;
+use state::StateData;
 use strmap::StrMap;
+use validate::{Validate, ValidationErrors};
+use xray::{TraceContext, TRACE_HEADER};
 
 /// API gateway pre-parsed http query string parameters
 pub(crate) struct QueryStringParameters(pub(crate) StrMap);
@@ -22,15 +43,255 @@ pub(crate) struct PathParameters(pub(crate) StrMap);
 /// [stage variables](https://docs.aws.amazon.com/apigateway/latest/developerguide/stage-variables.html)
 pub(crate) struct StageVariables(pub(crate) StrMap);
 
+/// The instant by which a request should be answered
+#[derive(Clone, Copy)]
+pub(crate) struct Deadline(pub(crate) Instant);
+
 /// Payload deserialization errors
 #[derive(Debug, Fail)]
 pub enum PayloadError {
     /// Returned when `application/json` bodies fail to deserialize a payload
-    #[fail(display = "failed to parse payload from application/json")]
-    Json(serde_json::Error),
+    #[fail(
+        display = "failed to parse {} from application/json: {}",
+        location, error
+    )]
+    Json {
+        /// The underlying serde_json error
+        error: serde_json::Error,
+        /// Where in the payload the error occurred
+        location: ErrorLocation,
+    },
     /// Returned when `application/x-www-form-urlencoded` bodies fail to deserialize a payload
-    #[fail(display = "failed to parse payload application/x-www-form-urlencoded")]
-    WwwFormUrlEncoded(SerdeError),
+    #[fail(
+        display = "failed to parse {} from application/x-www-form-urlencoded: {}",
+        location, error
+    )]
+    WwwFormUrlEncoded {
+        /// The underlying serde_urlencoded error
+        error: SerdeError,
+        /// Where in the payload the error occurred
+        location: ErrorLocation,
+    },
+    /// Returned when `application/xml` bodies fail to deserialize a payload
+    ///
+    /// Requires the `xml` cargo feature
+    #[cfg(feature = "xml")]
+    #[fail(display = "failed to parse payload from application/xml")]
+    Xml(serde_xml_rs::Error),
+    /// Returned when `application/msgpack` bodies fail to deserialize a payload
+    ///
+    /// Requires the `msgpack` cargo feature
+    #[cfg(feature = "msgpack")]
+    #[fail(display = "failed to parse payload from application/msgpack")]
+    MsgPack(rmp_serde::decode::Error),
+    /// Returned when `application/cbor` bodies fail to deserialize a payload
+    ///
+    /// Requires the `cbor` cargo feature
+    #[cfg(feature = "cbor")]
+    #[fail(display = "failed to parse payload from application/cbor")]
+    Cbor(serde_cbor::Error),
+    /// Returned when `application/yaml` bodies fail to deserialize a payload
+    ///
+    /// Requires the `yaml` cargo feature
+    #[cfg(feature = "yaml")]
+    #[fail(display = "failed to parse payload from application/yaml")]
+    Yaml(serde_yaml::Error),
+    /// Returned when a body is larger than the configured `Limits::max_body_bytes`
+    #[fail(display = "payload of {} bytes exceeds limit of {} bytes", size, limit)]
+    BodyTooLarge {
+        /// Size of the body in bytes
+        size: usize,
+        /// Configured limit in bytes
+        limit: usize,
+    },
+    /// Returned when `application/json` bodies nest deeper than the configured `Limits::max_json_depth`
+    #[fail(display = "payload exceeds json nesting depth limit of {}", limit)]
+    JsonTooDeep {
+        /// Configured depth limit
+        limit: usize,
+    },
+    /// Returned when `application/x-www-form-urlencoded` bodies contain more fields than the
+    /// configured `Limits::max_form_fields`
+    #[fail(
+        display = "payload of {} fields exceeds limit of {} fields",
+        count, limit
+    )]
+    TooManyFormFields {
+        /// Number of fields provided
+        count: usize,
+        /// Configured field limit
+        limit: usize,
+    },
+    /// Returned when a deserialized payload fails validation
+    #[fail(display = "{}", _0)]
+    Invalid(ValidationErrors),
+}
+
+impl PayloadError {
+    /// Return the location within the payload where deserialization failed, when known
+    ///
+    /// Locations are available for `application/json` and
+    /// `application/x-www-form-urlencoded` payloads
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{Body, RequestExt};
+    /// use std::collections::HashMap;
+    ///
+    /// let request = lando::http::Request::builder()
+    ///   .header("Content-Type", "application/json")
+    ///   .body(Body::from(r#"{"items":[1, "two"]}"#))
+    ///   .unwrap();
+    /// let err = request.payload::<HashMap<String, Vec<u32>>>().unwrap_err();
+    /// let location = err.location().unwrap();
+    /// assert_eq!(location.path(), "items[1]");
+    /// assert_eq!(location.line(), Some(1));
+    /// assert_eq!(location.expected(), Some("u32"));
+    /// ```
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            PayloadError::Json { location, .. } => Some(location),
+            PayloadError::WwwFormUrlEncoded { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+/// The location within a payload where deserialization failed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorLocation {
+    path: String,
+    line: Option<usize>,
+    column: Option<usize>,
+    expected: Option<String>,
+}
+
+impl ErrorLocation {
+    fn new(path: String, message: &str) -> Self {
+        ErrorLocation {
+            path,
+            expected: expected(message),
+            ..ErrorLocation::default()
+        }
+    }
+
+    /// Return the path of the field which failed to deserialize, i.e. `items[3].price`
+    ///
+    /// Errors which are not specific to a field, like malformed syntax
+    /// or trailing characters, have an empty path
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Return the line, starting at 1, where the error occurred for text formats which track lines
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Return the column, starting at 1, where the error occurred for text formats which track columns
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+
+    /// Return a description of the type that was expected, i.e. `u32` or `a string`, when known
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_ref().map(String::as_str)
+    }
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "payload")
+        } else {
+            write!(f, "payload field `{}`", self.path)
+        }
+    }
+}
+
+/// Render a path in the same form as validation error paths, i.e. `items[3].price`
+fn path_string(path: &Path) -> String {
+    let mut rendered = String::new();
+    for segment in path.iter() {
+        match segment {
+            Segment::Seq { index } => rendered.push_str(&format!("[{}]", index)),
+            Segment::Map { key: name } | Segment::Enum { variant: name } => {
+                if !rendered.is_empty() {
+                    rendered.push('.');
+                }
+                rendered.push_str(name);
+            }
+            Segment::Unknown => {
+                if !rendered.is_empty() {
+                    rendered.push('.');
+                }
+                rendered.push('?');
+            }
+        }
+    }
+    rendered
+}
+
+/// Extract the expected type from a serde error message,
+/// i.e. `invalid type: string "two", expected u32 at line 1 column 17`
+fn expected(message: &str) -> Option<String> {
+    let marker = ", expected ";
+    message.find(marker).map(|start| {
+        let expected = &message[start + marker.len()..];
+        expected
+            .rfind(" at line ")
+            .map(|end| &expected[..end])
+            .unwrap_or(expected)
+            .to_string()
+    })
+}
+
+fn json_error(path: String, error: serde_json::Error) -> PayloadError {
+    let location = ErrorLocation {
+        line: Some(error.line()).filter(|line| *line > 0),
+        column: Some(error.column()).filter(|_| error.line() > 0),
+        ..ErrorLocation::new(path, &error.to_string())
+    };
+    PayloadError::Json { error, location }
+}
+
+fn from_json<D>(body: &[u8]) -> Result<D, PayloadError>
+where
+    for<'de> D: Deserialize<'de>,
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let payload = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+        let path = path_string(err.path());
+        json_error(path, err.into_inner())
+    })?;
+    deserializer
+        .end()
+        .map_err(|err| json_error(String::new(), err))?;
+    Ok(payload)
+}
+
+fn from_form<D>(body: &[u8], decoding: FormDecoding) -> Result<D, PayloadError>
+where
+    for<'de> D: Deserialize<'de>,
+{
+    match decoding {
+        FormDecoding::Flat => serde_path_to_error::deserialize(
+            serde_urlencoded::Deserializer::new(form_urlencoded::parse(body)),
+        ),
+        FormDecoding::Nested => serde_path_to_error::deserialize(Node::parse(body)),
+    }
+    .map_err(|err| {
+        let location = ErrorLocation::new(path_string(err.path()), &err.inner().to_string());
+        PayloadError::WwwFormUrlEncoded {
+            error: err.into_inner(),
+            location,
+        }
+    })
+}
+
+/// Return the essence of a content type, without parameters like `charset`
+fn media_type(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or_default().trim()
 }
 
 /// Extentions for `lando::Request` structs that
@@ -89,18 +350,178 @@ pub trait RequestExt {
     /// Return request context data assocaited with the API gateway request
     fn request_context(&self) -> RequestContext;
 
+    /// Return application state of type `T` provided by
+    /// [State](state/struct.State.html) middleware. Requests without such state
+    /// yield `None`
+    fn state<T>(&self) -> Option<Arc<T>>
+    where
+        T: Send + Sync + 'static;
+
+    /// Configures instance with query string parameters
+    ///
+    /// This is intended for use in mock testing contexts.
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{Body, RequestExt, StrMap};
+    ///
+    /// let request = lando::http::Request::new(Body::Empty)
+    ///   .with_query_string_parameters(
+    ///     vec![("page", "2")].into_iter().collect::<StrMap>()
+    ///   );
+    /// assert_eq!(request.query_string_parameters().get("page"), Some("2"));
+    /// ```
+    fn with_query_string_parameters<Q>(self, parameters: Q) -> Self
+    where
+        Q: Into<StrMap>,
+        Self: Sized;
+
+    /// Configures instance with path parameters
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_path_parameters<P>(self, parameters: P) -> Self
+    where
+        P: Into<StrMap>,
+        Self: Sized;
+
+    /// Configures instance with stage variables
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_stage_variables<V>(self, variables: V) -> Self
+    where
+        V: Into<StrMap>,
+        Self: Sized;
+
+    /// Configures instance with request context
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_request_context(self, context: RequestContext) -> Self
+    where
+        Self: Sized;
+
+    /// Return the instant by which the request should be answered
+    ///
+    /// Requests handled in Lambda are given the invocation's deadline, derived from
+    /// the context's remaining time. [Timeout](struct.Timeout.html) middleware brings the
+    /// deadline forward by a safety margin. Use it to bound calls to downstream services.
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{Body, RequestExt};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let request = lando::http::Request::new(Body::Empty)
+    ///   .with_deadline(Instant::now() + Duration::from_secs(1));
+    /// assert!(request.remaining_time() <= Some(Duration::from_secs(1)));
+    /// ```
+    fn deadline(&self) -> Option<Instant>;
+
+    /// Return the time left before the request's deadline. Requests past their
+    /// deadline have no time left
+    fn remaining_time(&self) -> Option<Duration>;
+
+    /// Configures instance with a deadline
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_deadline(self, deadline: Instant) -> Self
+    where
+        Self: Sized;
+
+    /// Return the request's [X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/aws-xray.html)
+    /// trace context
+    ///
+    /// Inside Lambda, the context is parsed from the invocation's `_X_AMZN_TRACE_ID` environment
+    /// variable, whose parent is the function's own segment, falling back on the request's
+    /// `X-Amzn-Trace-Id` header, whose parent is the caller's. Requests which are not traced,
+    /// or carry an invalid trace header, yield `None`
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{Body, RequestExt};
+    ///
+    /// let request = lando::http::Request::builder()
+    ///   .header("X-Amzn-Trace-Id", "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1")
+    ///   .body(Body::Empty)
+    ///   .unwrap();
+    /// let trace = request.trace_context().unwrap();
+    /// assert_eq!(trace.root(), "1-5759e988-bd862e3fe1be46a994272793");
+    /// assert_eq!(trace.sampled(), Some(true));
+    /// ```
+    fn trace_context(&self) -> Option<TraceContext>;
+
+    /// Configures instance with a trace context
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_trace_context(self, trace: TraceContext) -> Self
+    where
+        Self: Sized;
+
+    /// Return the media type from `offered` that best matches the request's `Accept` header
+    ///
+    /// `Accept` media ranges are weighed by their `q` values, with more specific
+    /// ranges taking precedence over wildcards. Ties are broken by the order of `offered`,
+    /// so list your preferred types first. Requests without an `Accept` header yield
+    /// the first offered type. `None` is returned when nothing offered is acceptable.
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{Body, RequestExt};
+    ///
+    /// let request = lando::http::Request::builder()
+    ///   .header("Accept", "text/csv;q=0.9, application/json;q=0.5")
+    ///   .body(Body::Empty)
+    ///   .unwrap();
+    /// assert_eq!(
+    ///   request.negotiate(&["application/json", "text/csv"]),
+    ///   Some("text/csv")
+    /// );
+    /// ```
+    fn negotiate<'a>(&self, offered: &[&'a str]) -> Option<&'a str>;
+
     /// Return the Result of a payload parsed into a serde Deserializeable
     /// type
     ///
     /// Currently only `application/x-www-form-urlencoded`
     /// and `application/json` flavors of content type
-    /// are supported
+    /// are supported by default. `application/xml`, `application/msgpack`,
+    /// `application/cbor` and `application/yaml` are supported when lando's `xml`, `msgpack`,
+    /// `cbor` and `yaml` cargo features are enabled, respectively. Content type parameters,
+    /// like `charset`, are ignored
     ///
     /// A [PayloadError](enum.PayloadError.html) will be returned for undeserializable
     /// payloads. If no body is provided, `Ok(None)` will be returned.
+    ///
+    /// [Limits](struct.Limits.html) applied as middleware are enforced before deserializing
+    /// and the [FormDecoding](enum.FormDecoding.html) applied as middleware selects how
+    /// `application/x-www-form-urlencoded` payloads are decoded.
     fn payload<D>(&self) -> Result<Option<D>, PayloadError>
     where
         for<'de> D: Deserialize<'de>;
+
+    /// Return the Result of a payload parsed into a serde Deserializeable
+    /// type, enforcing the provided [Limits](struct.Limits.html) before deserializing
+    fn payload_with_limits<D>(&self, limits: &Limits) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>;
+
+    /// Return the Result of a payload parsed into a serde Deserializeable
+    /// type, decoding `application/x-www-form-urlencoded` payloads as specified by
+    /// `decoding`
+    fn payload_with_form_decoding<D>(
+        &self,
+        decoding: FormDecoding,
+    ) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>;
+
+    /// Return the Result of a payload parsed into a serde Deserializeable
+    /// type which is then [validated](validate/index.html)
+    ///
+    /// Payloads which deserialize but fail validation return `PayloadError::Invalid`, which
+    /// lists each invalid field
+    fn validated_payload<D>(&self) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de> + Validate;
 }
 
 impl RequestExt for HttpRequest<super::Body> {
@@ -130,25 +551,187 @@ impl RequestExt for HttpRequest<super::Body> {
             .unwrap_or_default()
     }
 
+    fn state<T>(&self) -> Option<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.extensions()
+            .get::<StateData<T>>()
+            .map(|state| state.0.clone())
+    }
+
+    fn with_query_string_parameters<Q>(mut self, parameters: Q) -> Self
+    where
+        Q: Into<StrMap>,
+    {
+        self.extensions_mut()
+            .insert(QueryStringParameters(parameters.into()));
+        self
+    }
+
+    fn with_path_parameters<P>(mut self, parameters: P) -> Self
+    where
+        P: Into<StrMap>,
+    {
+        self.extensions_mut()
+            .insert(PathParameters(parameters.into()));
+        self
+    }
+
+    fn with_stage_variables<V>(mut self, variables: V) -> Self
+    where
+        V: Into<StrMap>,
+    {
+        self.extensions_mut()
+            .insert(StageVariables(variables.into()));
+        self
+    }
+
+    fn with_request_context(mut self, context: RequestContext) -> Self {
+        self.extensions_mut().insert(context);
+        self
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.extensions()
+            .get::<Deadline>()
+            .map(|deadline| deadline.0)
+    }
+
+    fn remaining_time(&self) -> Option<Duration> {
+        self.deadline().map(|deadline| {
+            deadline
+                .checked_duration_since(Instant::now())
+                .unwrap_or_default()
+        })
+    }
+
+    fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.extensions_mut().insert(Deadline(deadline));
+        self
+    }
+
+    fn trace_context(&self) -> Option<TraceContext> {
+        if let Some(trace) = self.extensions().get::<TraceContext>() {
+            return Some(trace.clone());
+        }
+        TraceContext::from_env().or_else(|| {
+            self.headers()
+                .get(TRACE_HEADER)
+                .and_then(|header| header.to_str().ok())
+                .and_then(|header| header.parse().ok())
+        })
+    }
+
+    fn with_trace_context(mut self, trace: TraceContext) -> Self {
+        self.extensions_mut().insert(trace);
+        self
+    }
+
+    fn negotiate<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        negotiate::negotiate(negotiate::accept(self), offered)
+    }
+
     fn payload<D>(&self) -> Result<Option<D>, PayloadError>
     where
         for<'de> D: Deserialize<'de>,
     {
-        self.headers()
-            .get(CONTENT_TYPE)
-            .map(|ct| match ct.to_str() {
-                Ok("application/x-www-form-urlencoded") => {
-                    serde_urlencoded::from_bytes::<D>(self.body().as_ref())
-                        .map_err(PayloadError::WwwFormUrlEncoded)
-                        .map(Some)
-                }
-                Ok("application/json") => serde_json::from_slice::<D>(self.body().as_ref())
-                    .map_err(PayloadError::Json)
-                    .map(Some),
-                _ => Ok(None),
-            })
-            .unwrap_or_else(|| Ok(None))
+        let limits = self
+            .extensions()
+            .get::<Limits>()
+            .cloned()
+            .unwrap_or_default();
+        self.payload_with_limits(&limits)
     }
+
+    fn payload_with_limits<D>(&self, limits: &Limits) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        let decoding = self
+            .extensions()
+            .get::<FormDecoding>()
+            .cloned()
+            .unwrap_or_default();
+        decode(self, limits, decoding)
+    }
+
+    fn payload_with_form_decoding<D>(
+        &self,
+        decoding: FormDecoding,
+    ) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        let limits = self
+            .extensions()
+            .get::<Limits>()
+            .cloned()
+            .unwrap_or_default();
+        decode(self, &limits, decoding)
+    }
+
+    fn validated_payload<D>(&self) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de> + Validate,
+    {
+        match self.payload::<D>()? {
+            Some(payload) => payload
+                .validate()
+                .map(|_| Some(payload))
+                .map_err(PayloadError::Invalid),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn decode<D>(
+    request: &HttpRequest<super::Body>,
+    limits: &Limits,
+    decoding: FormDecoding,
+) -> Result<Option<D>, PayloadError>
+where
+    for<'de> D: Deserialize<'de>,
+{
+    if request.headers().contains_key(CONTENT_TYPE) {
+        limits.check_body(request.body())?;
+    }
+    let body = request.body().as_ref();
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|ct| match ct.to_str().map(media_type) {
+            Ok("application/x-www-form-urlencoded") => {
+                limits.check_form(body)?;
+                from_form::<D>(body, decoding).map(Some)
+            }
+            Ok("application/json") => {
+                limits.check_json(body)?;
+                from_json::<D>(body).map(Some)
+            }
+            #[cfg(feature = "xml")]
+            Ok("application/xml") | Ok("text/xml") => serde_xml_rs::from_reader::<_, D>(body)
+                .map_err(PayloadError::Xml)
+                .map(Some),
+            #[cfg(feature = "msgpack")]
+            Ok("application/msgpack") | Ok("application/x-msgpack") => {
+                rmp_serde::from_slice::<D>(body)
+                    .map_err(PayloadError::MsgPack)
+                    .map(Some)
+            }
+            #[cfg(feature = "cbor")]
+            Ok("application/cbor") => serde_cbor::from_slice::<D>(body)
+                .map_err(PayloadError::Cbor)
+                .map(Some),
+            #[cfg(feature = "yaml")]
+            Ok("application/yaml") | Ok("application/x-yaml") | Ok("text/yaml") => {
+                serde_yaml::from_slice::<D>(body)
+                    .map_err(PayloadError::Yaml)
+                    .map(Some)
+            }
+            _ => Ok(None),
+        })
+        .unwrap_or_else(|| Ok(None))
 }
 
 #[cfg(test)]
@@ -156,7 +739,7 @@ mod tests {
     use http::HeaderMap;
     use http::Request as HttpRequest;
     use std::collections::HashMap;
-    use {GatewayRequest, RequestExt, StrMap};
+    use {FormDecoding, GatewayRequest, RequestExt, StrMap};
 
     #[test]
     fn requests_have_query_string_ext() {
@@ -167,13 +750,13 @@ mod tests {
         let gwr: GatewayRequest = GatewayRequest {
             path: "/foo".into(),
             headers,
-            query_string_parameters: StrMap(query.clone().into()),
+            query_string_parameters: StrMap::from(query.clone()),
             ..GatewayRequest::default()
         };
         let actual = HttpRequest::from(gwr);
         assert_eq!(
             actual.query_string_parameters(),
-            StrMap(query.clone().into())
+            StrMap::from(query.clone())
         );
     }
 
@@ -255,4 +838,189 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn json_payload_errors_have_locations() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+        #[derive(Deserialize, Debug)]
+        struct Item {
+            price: f64,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Payload {
+            items: Vec<Item>,
+        }
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("{\"items\": [\n{\"price\": 1},\n{\"price\": \"free\"}]}".into()),
+            ..GatewayRequest::default()
+        };
+        let err = HttpRequest::from(gwr)
+            .payload::<Payload>()
+            .expect_err("expected payload error");
+        let location = err.location().expect("expected location");
+        assert_eq!(location.path(), "items[1].price");
+        assert_eq!(location.line(), Some(3));
+        assert_eq!(location.column(), Some(16));
+        assert_eq!(location.expected(), Some("f64"));
+    }
+
+    #[test]
+    fn json_syntax_errors_have_empty_paths() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("{} {}".into()),
+            ..GatewayRequest::default()
+        };
+        let err = HttpRequest::from(gwr)
+            .payload::<HashMap<String, String>>()
+            .expect_err("expected payload error");
+        let location = err.location().expect("expected location");
+        assert_eq!(location.path(), "");
+        assert_eq!(location.line(), Some(1));
+        assert_eq!(location.expected(), None);
+        assert_eq!(
+            err.to_string(),
+            "failed to parse payload from application/json: trailing characters at line 1 column 4"
+        );
+    }
+
+    #[test]
+    fn form_payload_errors_have_locations() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert(
+            "Content-Type",
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        #[derive(Deserialize, Debug)]
+        struct Payload {
+            foo: String,
+            baz: usize,
+        }
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("foo=bar&baz=two".into()),
+            ..GatewayRequest::default()
+        };
+        let err = HttpRequest::from(gwr)
+            .payload::<Payload>()
+            .expect_err("expected payload error");
+        let location = err.location().expect("expected location");
+        assert_eq!(location.path(), "baz");
+        assert_eq!(location.line(), None);
+        assert!(err.to_string().starts_with(
+            "failed to parse payload field `baz` from application/x-www-form-urlencoded"
+        ));
+    }
+
+    #[test]
+    fn form_map_payload_errors_have_locations() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert(
+            "Content-Type",
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("foo=1&baz=two".into()),
+            ..GatewayRequest::default()
+        };
+        let err = HttpRequest::from(gwr)
+            .payload::<HashMap<String, usize>>()
+            .expect_err("expected payload error");
+        let location = err.location().expect("expected location");
+        assert_eq!(location.path(), "baz");
+        assert_eq!(location.line(), None);
+    }
+
+    #[test]
+    fn requests_have_nested_form_parseable_payloads() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert(
+            "Content-Type",
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("user[ids][]=1&user[ids][]=2&user[name]=x".into()),
+            ..GatewayRequest::default()
+        };
+        let mut request = HttpRequest::from(gwr);
+        assert!(request
+            .payload::<HashMap<String, HashMap<String, Vec<String>>>>()
+            .is_err());
+        request.extensions_mut().insert(FormDecoding::Nested);
+        let err = request
+            .payload::<HashMap<String, HashMap<String, Vec<u8>>>>()
+            .expect_err("expected payload error");
+        assert_eq!(err.location().map(|l| l.path()), Some("user.name[0]"));
+        let payload = request
+            .payload::<HashMap<String, HashMap<String, Vec<String>>>>()
+            .expect("failed to parse payload")
+            .expect("expected payload");
+        assert_eq!(payload["user"]["ids"], vec!["1", "2"]);
+        assert_eq!(payload["user"]["name"], vec!["x"]);
+    }
+
+    #[test]
+    fn requests_ignore_content_type_parameters() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert(
+            "Content-Type",
+            "application/json; charset=utf-8".parse().unwrap(),
+        );
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some(r#"{"foo":"bar"}"#.into()),
+            ..GatewayRequest::default()
+        };
+        let actual = HttpRequest::from(gwr);
+        let mut expected = HashMap::new();
+        expected.insert("foo".to_string(), "bar".to_string());
+        let payload: Option<HashMap<String, String>> = actual.payload().unwrap_or_default();
+        assert_eq!(payload, Some(expected))
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn requests_have_yaml_parseable_payloads() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert("Content-Type", "application/yaml".parse().unwrap());
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Payload {
+            foo: String,
+            baz: usize,
+        }
+        let gwr: GatewayRequest = GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            body: Some("foo: bar\nbaz: 2".into()),
+            ..GatewayRequest::default()
+        };
+        let actual = HttpRequest::from(gwr);
+        let payload: Option<Payload> = actual.payload().unwrap_or_default();
+        assert_eq!(
+            payload,
+            Some(Payload {
+                foo: "bar".into(),
+                baz: 2
+            })
+        )
+    }
 }
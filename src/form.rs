@@ -0,0 +1,474 @@
+//! Decoding of `application/x-www-form-urlencoded` payloads with bracket notation
+
+// Std
+use std::collections::BTreeMap;
+
+// Third Party
+use serde::de::value::{Error as SerdeError, MapDeserializer, SeqDeserializer};
+use serde::de::{self, Error, IntoDeserializer, Visitor};
+use url::form_urlencoded;
+
+// Ours
+use middleware::Middleware;
+use {Body, Request, Response, Result};
+
+/// How `application/x-www-form-urlencoded` payloads are decoded by
+/// [RequestExt#payload](trait.RequestExt.html#tymethod.payload)
+///
+/// `Flat` decoding, the default, supports structs whose fields are strings or primitives.
+///
+/// `Nested` decoding supports the bracket notation commonly used by html forms.
+///
+/// * `user[name]=x` decodes into nested structs and maps
+/// * `user[tags][]=a&user[tags][]=b` and `tags=a&tags=b` decode into sequences
+/// * `ids[1]=b&ids[0]=a` decodes into sequences ordered by index
+///
+/// Decoding may be selected per call with
+/// [RequestExt#payload_with_form_decoding](trait.RequestExt.html#tymethod.payload_with_form_decoding)
+/// or for all payloads a handler deserializes by applying a `FormDecoding` as
+/// [Middleware](middleware/trait.Middleware.html).
+///
+/// ```rust
+/// # extern crate lando;
+/// # #[macro_use] extern crate serde_derive;
+/// use lando::{Body, FormDecoding, RequestExt};
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///   name: String,
+///   tags: Vec<String>
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///   user: User
+/// }
+///
+/// # fn main() {
+/// let request = lando::http::Request::builder()
+///   .header("Content-Type", "application/x-www-form-urlencoded")
+///   .body(Body::from("user[name]=x&user[tags][]=a&user[tags][]=b"))
+///   .unwrap();
+/// let signup = request
+///   .payload_with_form_decoding::<Signup>(FormDecoding::Nested)
+///   .unwrap()
+///   .unwrap();
+/// assert_eq!(signup.user.tags, vec!["a", "b"]);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormDecoding {
+    /// Decode `key=value` pairs into flat structures
+    Flat,
+    /// Decode bracket notation keys into nested structures and sequences
+    Nested,
+}
+
+impl Default for FormDecoding {
+    fn default() -> Self {
+        FormDecoding::Flat
+    }
+}
+
+impl Middleware for FormDecoding {
+    fn handle<N>(&self, mut request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        request.extensions_mut().insert(*self);
+        next(request)
+    }
+}
+
+/// A tree of decoded form values
+#[derive(Debug, PartialEq)]
+pub(crate) enum Node {
+    /// One or more values provided for the same key
+    Values(Vec<String>),
+    /// Named children in the order they were first provided
+    Children(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Return a tree of values from form encoded bytes
+    pub(crate) fn parse(input: &[u8]) -> Self {
+        let mut children = Vec::new();
+        for (key, value) in form_urlencoded::parse(input) {
+            insert(&mut children, &segments(&key), value.into_owned());
+        }
+        Node::Children(children)
+    }
+
+    fn invalid_type<E>(&self, expected: &dyn de::Expected) -> E
+    where
+        E: de::Error,
+    {
+        let unexpected = match self {
+            Node::Values(_) => de::Unexpected::Str("a value"),
+            Node::Children(_) => de::Unexpected::Map,
+        };
+        E::invalid_type(unexpected, expected)
+    }
+}
+
+/// Split a key like `user[tags][]` into segments `user`, `tags` and an empty
+/// segment denoting a new sequence item
+fn segments(key: &str) -> Vec<&str> {
+    match key.find('[') {
+        Some(start) if start > 0 && key.ends_with(']') => {
+            let mut segments = vec![&key[..start]];
+            segments.extend(key[start + 1..key.len() - 1].split("]["));
+            segments
+        }
+        _ => vec![key],
+    }
+}
+
+fn insert(children: &mut Vec<(String, Node)>, segments: &[&str], value: String) {
+    let (key, rest) = match segments.split_first() {
+        Some(split) => split,
+        _ => return,
+    };
+    // empty segments always append a new item
+    let existing = if key.is_empty() {
+        None
+    } else {
+        children.iter().position(|(name, node)| {
+            name == key
+                && match (node, rest.is_empty()) {
+                    (Node::Values(_), true) | (Node::Children(_), false) => true,
+                    _ => false,
+                }
+        })
+    };
+    let index = existing.unwrap_or_else(|| {
+        let node = if rest.is_empty() {
+            Node::Values(Vec::new())
+        } else {
+            Node::Children(Vec::new())
+        };
+        children.push((key.to_string(), node));
+        children.len() - 1
+    });
+    match children[index].1 {
+        Node::Values(ref mut values) => values.push(value),
+        Node::Children(ref mut children) => insert(children, rest, value),
+    }
+}
+
+/// Return the items of a node when treated as a sequence. Children keyed by
+/// index are ordered by index, otherwise by the order they were provided.
+fn items(node: Node) -> Vec<Node> {
+    match node {
+        Node::Values(values) => values
+            .into_iter()
+            .map(|value| Node::Values(vec![value]))
+            .collect(),
+        Node::Children(children) => {
+            let indexed = children
+                .iter()
+                .map(|(key, _)| key.parse::<usize>().ok())
+                .collect::<Option<Vec<_>>>();
+            match indexed {
+                Some(indices) => indices
+                    .into_iter()
+                    .zip(children.into_iter().map(|(_, node)| node))
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(_, node)| node)
+                    .collect(),
+                _ => children.into_iter().map(|(_, node)| node).collect(),
+            }
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    Node::Values(mut values) => match values.pop() {
+                        Some(value) => visitor.$visit(value.parse().map_err(SerdeError::custom)?),
+                        _ => Err(SerdeError::custom("missing value")),
+                    },
+                    node => Err(node.invalid_type(&visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Node {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = SerdeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Values(ref values) if values.len() > 1 => self.deserialize_seq(visitor),
+            Node::Values(_) => self.deserialize_string(visitor),
+            Node::Children(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // repeated keys decoded as a single value take the last value
+            Node::Values(mut values) => visitor.visit_string(values.pop().unwrap_or_default()),
+            node => Err(node.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut items = SeqDeserializer::new(items(self).into_iter());
+        let value = visitor.visit_seq(&mut items)?;
+        items.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Children(children) => {
+                let mut entries = MapDeserializer::new(
+                    children
+                        .into_iter()
+                        .map(|(key, node)| (Node::Values(vec![key]), node)),
+                );
+                let value = visitor.visit_map(&mut entries)?;
+                entries.end()?;
+                Ok(value)
+            }
+            node => Err(node.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // only unit variants may be named by a value
+            Node::Values(mut values) => {
+                visitor.visit_enum(values.pop().unwrap_or_default().into_deserializer())
+            }
+            node => Err(node.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> ::std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    fn decode<'de, D>(input: &str) -> ::std::result::Result<D, SerdeError>
+    where
+        D: Deserialize<'de>,
+    {
+        D::deserialize(Node::parse(input.as_bytes()))
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct User {
+        name: String,
+        age: Option<u8>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Signup {
+        user: User,
+        accept: bool,
+    }
+
+    #[test]
+    fn splits_segments() {
+        assert_eq!(segments("user"), vec!["user"]);
+        assert_eq!(segments("user[tags][]"), vec!["user", "tags", ""]);
+        assert_eq!(segments("[user]"), vec!["[user]"]);
+        assert_eq!(segments("user[name"), vec!["user[name"]);
+    }
+
+    #[test]
+    fn decodes_bracket_notation() {
+        assert_eq!(
+            decode::<Signup>("user[name]=x+y&user[tags][]=a&user%5Btags%5D%5B%5D=b&accept=true")
+                .expect("failed to decode"),
+            Signup {
+                user: User {
+                    name: "x y".into(),
+                    age: None,
+                    tags: vec!["a".into(), "b".into()],
+                },
+                accept: true,
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_repeated_keys_into_sequences() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Filter {
+            tag: Vec<String>,
+            id: Vec<u32>,
+        }
+        assert_eq!(
+            decode::<Filter>("tag=a&tag=b&id=1").expect("failed to decode"),
+            Filter {
+                tag: vec!["a".into(), "b".into()],
+                id: vec![1],
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_indexed_sequences_in_order() {
+        assert_eq!(
+            decode::<HashMap<String, Vec<User>>>(
+                "users[1][name]=b&users[1][tags][0]=y&users[0][name]=a&users[0][age]=3&users[1][tags][]=z"
+            )
+            .expect("failed to decode")
+            .remove("users"),
+            Some(vec![
+                User {
+                    name: "a".into(),
+                    age: Some(3),
+                    tags: vec![],
+                },
+                User {
+                    name: "b".into(),
+                    age: None,
+                    tags: vec!["y".into(), "z".into()],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        assert!(decode::<Signup>("user[name]=x&user[age]=old&accept=true").is_err());
+        assert!(decode::<Signup>("user=x&accept=true").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Serde data formats lando can serialize response bodies to
+
+// Third Party
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+#[cfg(feature = "msgpack")]
+use rmp_serde;
+use serde::Serialize;
+#[cfg(feature = "cbor")]
+use serde_cbor;
+use serde_json;
+#[cfg(feature = "xml")]
+use serde_xml_rs;
+#[cfg(feature = "yaml")]
+use serde_yaml;
+
+// Ours
+use super::{Body, IntoResponse, Response};
+
+/// Return the media types lando is able to serialize values to, in order of preference
+pub(crate) fn serializable() -> Vec<&'static str> {
+    let mut media_types = vec!["application/json"];
+    #[cfg(feature = "xml")]
+    media_types.push("application/xml");
+    #[cfg(feature = "yaml")]
+    media_types.push("application/yaml");
+    #[cfg(feature = "msgpack")]
+    media_types.push("application/msgpack");
+    #[cfg(feature = "cbor")]
+    media_types.push("application/cbor");
+    media_types
+}
+
+/// Serialize a value into a body for one of the `serializable()` media types
+pub(crate) fn serialize<T>(media_type: &str, value: &T) -> Result<Body, String>
+where
+    T: Serialize,
+{
+    match media_type {
+        "application/json" => serde_json::to_string(value)
+            .map(Body::from)
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "xml")]
+        "application/xml" => serde_xml_rs::to_string(value)
+            .map(Body::from)
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "yaml")]
+        "application/yaml" => serde_yaml::to_string(value)
+            .map(Body::from)
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "msgpack")]
+        "application/msgpack" => rmp_serde::to_vec_named(value)
+            .map(Body::from)
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "cbor")]
+        "application/cbor" => serde_cbor::to_vec(value)
+            .map(Body::from)
+            .map_err(|e| e.to_string()),
+        other => Err(format!("unsupported media type {}", other)),
+    }
+}
+
+/// Serialize a value into a response with the provided media type
+///
+/// Values which fail to serialize result in a `500 Internal Server Error` response
+pub(crate) fn respond<T>(media_type: &'static str, value: &T) -> Response<Body>
+where
+    T: Serialize,
+{
+    respond_with(media_type, serialize(media_type, value))
+}
+
+/// Return a response with a rendered body and the provided media type
+///
+/// Bodies which failed to render, or invalid media types, result in a
+/// `500 Internal Server Error` response
+pub(crate) fn respond_with(media_type: &str, body: Result<Body, String>) -> Response<Body> {
+    let mut response = Response::new(Body::Empty);
+    match (body, HeaderValue::from_str(media_type)) {
+        (Ok(body), Ok(media_type)) => {
+            response.headers_mut().insert(CONTENT_TYPE, media_type);
+            *response.body_mut() = body;
+        }
+        _ => *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR,
+    }
+    response
+}
+
+/// A value serialized as an `application/json` response
+///
+/// Any `Serialize` type may be wrapped, avoiding an intermediate
+/// `serde_json::Value`. Values which fail to serialize result in a `500 Internal Server Error`
+/// response rather than a panic.
+///
+/// # example
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// # #[macro_use] extern crate serde_derive;
+/// use lando::Json;
+///
+/// #[derive(Serialize)]
+/// struct Greeting {
+///   message: &'static str
+/// }
+///
+/// gateway!(|_, _| {
+///   Ok(Json(Greeting { message: "hello" }))
+/// });
+/// # fn main() { }
+/// ```
+pub struct Json<T>(pub T);
+
+impl<T> IntoResponse for Json<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        respond("application/json", &self.0)
+    }
+}
+
+/// A value serialized as an `application/json` response with a given status code
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// use lando::http::StatusCode;
+/// use lando::JsonWithStatus;
+///
+/// gateway!(|_, _| {
+///   Ok(JsonWithStatus(StatusCode::CREATED, vec!["one", "two"]))
+/// });
+/// # fn main() { }
+/// ```
+pub struct JsonWithStatus<T>(pub StatusCode, pub T);
+
+impl<T> IntoResponse for JsonWithStatus<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        let JsonWithStatus(status, value) = self;
+        let mut response = respond("application/json", &value);
+        if response.status().is_success() {
+            *response.status_mut() = status;
+        }
+        response
+    }
+}
+
+/// A value serialized as an `application/xml` response
+///
+/// Requires the `xml` cargo feature
+#[cfg(feature = "xml")]
+pub struct Xml<T>(pub T);
+
+#[cfg(feature = "xml")]
+impl<T> IntoResponse for Xml<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        respond("application/xml", &self.0)
+    }
+}
+
+/// A value serialized as an `application/yaml` response
+///
+/// Requires the `yaml` cargo feature
+#[cfg(feature = "yaml")]
+pub struct Yaml<T>(pub T);
+
+#[cfg(feature = "yaml")]
+impl<T> IntoResponse for Yaml<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        respond("application/yaml", &self.0)
+    }
+}
+
+/// A value serialized as an `application/msgpack` response
+///
+/// Requires the `msgpack` cargo feature
+#[cfg(feature = "msgpack")]
+pub struct MsgPack<T>(pub T);
+
+#[cfg(feature = "msgpack")]
+impl<T> IntoResponse for MsgPack<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        respond("application/msgpack", &self.0)
+    }
+}
+
+/// A value serialized as an `application/cbor` response
+///
+/// Requires the `cbor` cargo feature
+#[cfg(feature = "cbor")]
+pub struct Cbor<T>(pub T);
+
+#[cfg(feature = "cbor")]
+impl<T> IntoResponse for Cbor<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        respond("application/cbor", &self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn serializes_json() {
+        let mut value = HashMap::new();
+        value.insert("foo", "bar");
+        assert_eq!(
+            serialize("application/json", &value),
+            Ok(Body::from(r#"{"foo":"bar"}"#))
+        );
+    }
+
+    #[test]
+    fn json_with_status_into_response() {
+        let response = JsonWithStatus(StatusCode::CREATED, vec!["foo"]).into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.body(), &Body::from(r#"["foo"]"#));
+    }
+
+    #[test]
+    fn json_serialization_failures_are_server_errors() {
+        let mut value = HashMap::new();
+        value.insert(vec![1], "non-string keys are not json serializable");
+        let response = JsonWithStatus(StatusCode::CREATED, value).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.body(), &Body::Empty);
+    }
+
+    #[test]
+    fn rejects_unsupported_media_types() {
+        assert!(serialize("image/png", &()).is_err());
+        // form payloads are deserialized but not rendered
+        assert!(serialize("application/x-www-form-urlencoded", &()).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_into_response() {
+        let mut value = HashMap::new();
+        value.insert("foo", "bar");
+        let response = Yaml(value).into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/yaml");
+        assert_eq!(
+            serde_yaml::from_slice::<HashMap<String, String>>(response.body()).ok(),
+            Some(
+                vec![("foo".to_string(), "bar".to_string())]
+                    .into_iter()
+                    .collect()
+            )
+        );
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_into_response() {
+        let response = MsgPack(vec![1, 2, 3]).into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/msgpack");
+        match response.body() {
+            Body::Binary(_) => (),
+            not => assert!(false, "expected Body::Binary(...) got {:?}", not),
+        }
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_into_response() {
+        let response = Cbor(vec![1, 2, 3]).into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/cbor");
+        assert_eq!(
+            serde_cbor::from_slice::<Vec<u8>>(response.body()).ok(),
+            Some(vec![1, 2, 3])
+        );
+    }
+}
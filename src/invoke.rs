@@ -0,0 +1,352 @@
+//! Run handlers locally against API gateway events
+//!
+//! Lambda functions built with lando are dynamic libraries loaded by AWS' Python runtime.
+//! To exercise a handler on your own machine, add a small binary to your crate which
+//! passes it to [run](fn.run.html)
+//!
+//! ```rust,no_run
+//! # extern crate lando;
+//! // src/bin/lando.rs
+//! use lando::{IntoResponse, LambdaContext, Request, Result};
+//!
+//! fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   Ok("👋")
+//! }
+//!
+//! fn main() {
+//!   lando::invoke::run(handler)
+//! }
+//! ```
+//!
+//! then invoke it with an event file, or an event piped to stdin
+//!
+//! ```sh
+//! $ cargo run --bin lando -- invoke --event tests/data/proxy_request.json
+//! $ cat tests/data/proxy_request.json | cargo run --bin lando -- invoke
+//! ```
+//!
+//! The `GatewayResponse` json that would be returned to API gateway is printed to stdout and
+//! the time the invocation took to stderr. The process exits with a status of `1`
+//! when the handler returns an error or panics and `2` when the event can not be read.
+//! [testing::EventBuilder](../testing/struct.EventBuilder.html) produces events to invoke with.
+
+// Std
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::time::{Duration, Instant};
+
+// Third Party
+use serde_json::{self, Value};
+
+// Ours
+use request;
+use {call, panic_message, IntoResponse, LambdaContext, Request, Result};
+
+/// Errors invoking a handler locally
+#[derive(Debug, Fail)]
+pub enum InvokeError {
+    /// Returned for unrecognized command line arguments
+    #[fail(display = "{}", message)]
+    Usage {
+        /// A description of the problem
+        message: String,
+    },
+    /// Returned when an event could not be read
+    #[fail(display = "failed to read event from {}: {}", source, error)]
+    Io {
+        /// The event file path or `stdin`
+        source: String,
+        /// The underlying error
+        #[cause]
+        error: io::Error,
+    },
+    /// Returned when an event is not a valid API gateway event
+    #[fail(display = "invalid API gateway event: {}", error)]
+    Event {
+        /// The underlying error
+        #[cause]
+        error: serde_json::Error,
+    },
+    /// Returned when the handler fails
+    #[fail(display = "handler failed: {}", message)]
+    Handler {
+        /// A description of the handler's error
+        message: String,
+    },
+}
+
+impl InvokeError {
+    /// Return the status `run` exits with when this error occurs
+    fn exit_code(&self) -> i32 {
+        match self {
+            InvokeError::Handler { .. } => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// The outcome of a local invocation
+#[derive(Debug)]
+pub struct Invocation {
+    response: Value,
+    elapsed: Duration,
+}
+
+impl Invocation {
+    /// Return the `GatewayResponse` json that would be returned to API gateway
+    pub fn response(&self) -> &Value {
+        &self.response
+    }
+
+    /// Return the time spent deserializing the event, handling it and serializing the response
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Invoke a handler with the json of an API gateway event and a context
+///
+/// ```rust
+/// # extern crate lando;
+/// use lando::invoke::invoke;
+/// use lando::testing::{EventBuilder, Trigger};
+/// use lando::LambdaContext;
+///
+/// # fn main() {
+/// let event = EventBuilder::new(Trigger::RestApi).to_json();
+/// let invocation = invoke(event.as_bytes(), LambdaContext::default(), |_, _| Ok("hi"))
+///   .expect("failed to invoke handler");
+/// assert_eq!(invocation.response()["body"], "hi");
+/// # }
+/// ```
+pub fn invoke<F, R>(
+    event: &[u8],
+    context: LambdaContext,
+    handler: F,
+) -> ::std::result::Result<Invocation, InvokeError>
+where
+    F: FnOnce(Request, LambdaContext) -> Result<R>,
+    R: IntoResponse,
+{
+    let start = Instant::now();
+    let request = request::from_slice(event).map_err(|error| InvokeError::Event { error })?;
+    // unlike in Lambda, where they are answered with a 500, panics fail local invocations
+    let handler = move |request, context| {
+        panic::catch_unwind(AssertUnwindSafe(move || handler(request, context)))
+            .unwrap_or_else(|payload| Err(format!("panicked: {}", panic_message(&payload)).into()))
+    };
+    let response = call(handler, request, context)
+        .map_err(|error| error.to_string())
+        .and_then(|response| serde_json::to_value(response).map_err(|error| error.to_string()))
+        .map_err(|message| InvokeError::Handler { message })?;
+    Ok(Invocation {
+        response,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Invoke a handler with an event named by command line arguments, print the response
+/// and exit
+///
+/// Arguments take the form `[invoke] [--event <path>]`. Events are read from
+/// stdin when no path, or a path of `-`, is provided.
+pub fn run<F, R>(handler: F) -> !
+where
+    F: FnOnce(Request, LambdaContext) -> Result<R>,
+    R: IntoResponse,
+{
+    process::exit(match execute(env::args().skip(1), handler) {
+        Ok(invocation) => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let printed = serde_json::to_writer_pretty(&mut stdout, invocation.response())
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(stdout));
+            eprintln!("invoked in {}", millis(invocation.elapsed()));
+            if printed.is_ok() {
+                0
+            } else {
+                1
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            err.exit_code()
+        }
+    })
+}
+
+fn execute<A, F, R>(args: A, handler: F) -> ::std::result::Result<Invocation, InvokeError>
+where
+    A: IntoIterator<Item = String>,
+    F: FnOnce(Request, LambdaContext) -> Result<R>,
+    R: IntoResponse,
+{
+    let event = read(event_path(args)?.as_ref().map(String::as_str))?;
+    let context = LambdaContext::default().with_aws_request_id("local");
+    invoke(&event, context, handler)
+}
+
+/// Return the event path named by arguments, if any
+fn event_path<A>(args: A) -> ::std::result::Result<Option<String>, InvokeError>
+where
+    A: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    if args.peek().map_or(false, |arg| arg == "invoke") {
+        args.next();
+    }
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--event" | "-e" => match args.next() {
+                Some(value) => path = Some(value),
+                _ => {
+                    return Err(InvokeError::Usage {
+                        message: format!("{} requires a path", arg),
+                    })
+                }
+            },
+            _ => {
+                return Err(InvokeError::Usage {
+                    message: format!(
+                        "unexpected argument {}. usage: [invoke] [--event <path>]",
+                        arg
+                    ),
+                })
+            }
+        }
+    }
+    Ok(path.filter(|path| path != "-"))
+}
+
+fn read(path: Option<&str>) -> ::std::result::Result<Vec<u8>, InvokeError> {
+    match path {
+        Some(path) => fs::read(path).map_err(|error| InvokeError::Io {
+            source: path.into(),
+            error,
+        }),
+        _ => {
+            let mut event = Vec::new();
+            io::stdin()
+                .read_to_end(&mut event)
+                .map_err(|error| InvokeError::Io {
+                    source: "stdin".into(),
+                    error,
+                })?;
+            Ok(event)
+        }
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!(
+        "{:.3}ms",
+        duration.as_secs() as f64 * 1000.0 + f64::from(duration.subsec_nanos()) / 1_000_000.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use testing::{EventBuilder, Trigger};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_event_paths() {
+        assert_eq!(event_path(args(&[])).unwrap(), None);
+        assert_eq!(event_path(args(&["invoke", "-e", "-"])).unwrap(), None);
+        assert_eq!(
+            event_path(args(&["invoke", "--event", "event.json"])).unwrap(),
+            Some("event.json".into())
+        );
+        assert_eq!(
+            event_path(args(&["-e", "event.json"])).unwrap(),
+            Some("event.json".into())
+        );
+        assert!(event_path(args(&["--event"])).is_err());
+        assert!(event_path(args(&["--pretty"])).is_err());
+    }
+
+    #[test]
+    fn invokes_handlers_with_event_files() {
+        let path = env::temp_dir().join(format!(
+            "lando_{}_invokes_handlers_with_event_files.json",
+            process::id()
+        ));
+        fs::write(
+            &path,
+            EventBuilder::new(Trigger::RestApi)
+                .path("/orders")
+                .to_json(),
+        )
+        .expect("failed to write event");
+        let invocation = execute(
+            args(&["invoke", "--event", &path.to_string_lossy()]),
+            |request, context| {
+                assert_eq!(context.aws_request_id(), "local");
+                Ok(request.uri().path().to_owned())
+            },
+        );
+        // removed before asserting so failures do not leave the file behind
+        let _ = fs::remove_file(&path);
+        let invocation = invocation.expect("failed to invoke handler");
+        assert_eq!(
+            invocation.response(),
+            &json!({
+                "statusCode": 200,
+                "body": "/orders"
+            })
+        );
+    }
+
+    #[test]
+    fn reports_handler_errors() {
+        let event = EventBuilder::new(Trigger::RestApi).to_json();
+        match invoke(event.as_bytes(), LambdaContext::default(), |_, _| {
+            Err::<(), _>("boom".into())
+        }) {
+            Err(err @ InvokeError::Handler { .. }) => {
+                assert_eq!(err.to_string(), "handler failed: boom");
+                assert_eq!(err.exit_code(), 1)
+            }
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_handler_panics() {
+        let event = EventBuilder::new(Trigger::RestApi).to_json();
+        match invoke(
+            event.as_bytes(),
+            LambdaContext::default(),
+            |_, _| -> Result<()> { panic!("boom") },
+        ) {
+            Err(InvokeError::Handler { message }) => assert_eq!(message, "panicked: boom"),
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_invalid_events() {
+        match invoke(b"{}", LambdaContext::default(), |_, _| Ok(())) {
+            Err(err @ InvokeError::Event { .. }) => assert_eq!(err.exit_code(), 2),
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+        match execute(args(&["--event", "missing.json"]), |_, _| Ok(())) {
+            Err(err @ InvokeError::Io { .. }) => {
+                assert!(err
+                    .to_string()
+                    .starts_with("failed to read event from missing.json"))
+            }
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+    }
+}
@@ -44,6 +44,7 @@
 //! ```
 //!
 //! Alternatively, you can also just attribute a bare handler `fn` with `#[lando]`
+//! and export it with [export!](macro.export.html)
 //!
 //! ```rust
 //! # #[macro_use] extern crate lando;
@@ -56,6 +57,9 @@
 //! ) -> Result<impl IntoResponse> {
 //!     Ok(())
 //! }
+//!
+//! export!(handler);
+//! # fn main() { }
 //! ```
 //!
 //! # Packaging functions
@@ -85,6 +89,25 @@
 //! by building in an environment similar to Lambda's. [This Docker
 //! container](https://hub.docker.com/r/softprops/lambda-rust/) faithfully reproduces the AWS Lambda Python 3.6 runtime.
 //!
+//! # Optional features
+//!
+//! In addition to `application/json`, and `application/x-www-form-urlencoded` request payloads,
+//! lando can deserialize request payloads and serialize responses in the following formats
+//! when their cargo features are enabled
+//!
+//! * `xml` - `application/xml`
+//! * `msgpack` - `application/msgpack`
+//! * `cbor` - `application/cbor`
+//! * `yaml` - `application/yaml`
+//!
+//! ```toml
+//! [dependencies]
+//! lando = { version = "0.2", features = ["yaml"] }
+//! ```
+//!
+//! The `tokio` feature drives asynchronous handlers on a tokio runtime, providing the reactor
+//! and timers tokio based clients depend on. See [executor](executor/index.html).
+//!
 #[cfg(test)]
 #[macro_use]
 extern crate pretty_assertions;
@@ -105,43 +128,99 @@ extern crate failure;
 extern crate failure_derive;
 // re-export for convenience
 pub extern crate http;
+#[macro_use]
+extern crate lazy_static;
+extern crate log;
 extern crate paste;
 // re-export for use in gateway! macro
 #[doc(hidden)]
 pub use paste::item as paste_item;
+// re-export for use in routes! macro
+#[doc(hidden)]
+pub use paste::expr as paste_expr;
+#[macro_use]
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "cbor")]
+extern crate serde_cbor;
 extern crate serde_json;
+extern crate serde_path_to_error;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
 extern crate serde_urlencoded;
+#[cfg(feature = "xml")]
+extern crate serde_xml_rs;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+extern crate url;
 
 /// Export #[lando] proc macro
 pub extern crate lando_attr;
 pub use lando_attr::*;
 
 // Std
+use std::any::Any;
 use std::error::Error as StdError;
+use std::panic::{self, AssertUnwindSafe};
 use std::result::Result as StdResult;
 
 // Third Party
 use cpython::Python;
 #[doc(hidden)]
 pub use cpython::{PyObject, PyResult};
-pub use crowbar::LambdaContext;
 
 // Ours
 
 mod body;
+mod context;
+pub mod executor;
 mod ext;
+mod form;
+mod format;
+pub mod invoke;
+mod limits;
+pub mod logging;
+pub mod middleware;
+mod negotiate;
+mod problem;
+mod range;
 pub mod request;
-mod response;
+pub mod response;
+pub mod router;
+pub mod runtime;
+pub mod state;
 mod strmap;
+mod timeout;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod validate;
+pub mod xray;
 
 pub use body::Body;
-pub use ext::{PayloadError, RequestExt};
+pub use context::{CognitoIdentity, LambdaContext};
+pub use ext::{ErrorLocation, PayloadError, RequestExt};
+#[cfg(feature = "cbor")]
+pub use format::Cbor;
+pub use form::FormDecoding;
+pub use format::{Json, JsonWithStatus};
+pub use limits::Limits;
+#[cfg(feature = "msgpack")]
+pub use format::MsgPack;
+#[cfg(feature = "xml")]
+pub use format::Xml;
+#[cfg(feature = "yaml")]
+pub use format::Yaml;
+pub use negotiate::Negotiated;
+pub use problem::Problem;
+pub use range::{ranged, ranged_with_content_type};
 //  for benches only!
 pub use request::GatewayRequest;
-pub use strmap::StrMap;
+pub use strmap::{ParseError, StrMap};
+pub use timeout::{DeadlineExceeded, Timeout};
+pub use validate::{FieldError, Validate, ValidationErrors};
 
 /// A re-exported version of `http::Request` with a type
 /// parameter for body fixed to type [lando::Body](enum.Body.html)
@@ -156,7 +235,8 @@ pub type Result<T> = StdResult<T, Box<StdError>>;
 /// A conversion of self into a `Response`
 ///
 /// Implementations for `Response<B> where B: Into<Body>`,
-/// `B where B: Into<Body>` and `serde_json::Value` are provided
+/// `B where B: Into<Body>` and `serde_json::Value` are provided. Any `Serialize` type
+/// may be returned as JSON by wrapping it with [lando::Json](struct.Json.html)
 ///
 /// # example
 ///
@@ -194,14 +274,7 @@ where
 
 impl IntoResponse for serde_json::Value {
     fn into_response(self) -> Response<Body> {
-        Response::builder()
-            .header(http::header::CONTENT_TYPE, "application/json")
-            .body(
-                serde_json::to_string(&self)
-                    .expect("unable to serialize serde_json::Value")
-                    .into(),
-            )
-            .expect("unable to build http::Response")
+        Json(self).into_response()
     }
 }
 
@@ -221,15 +294,74 @@ where
     crowbar::handler(
         py,
         |event, ctx| {
-            let apigw = serde_json::from_value::<request::GatewayRequest>(event)?;
-            func(Request::from(apigw), ctx)
-                .map(|into| response::GatewayResponse::from(into.into_response()))
+            // borrow strings from the event rather than copying them
+            let apigw = <request::GatewayRequest as serde::Deserialize>::deserialize(&event)?;
+            call(func, Request::from(apigw), LambdaContext::from(&ctx))
         },
         py_event,
         py_context,
     )
 }
 
+/// Invoke a handler, isolating panics from the Python runtime
+///
+/// Unwinding across the FFI boundary into Python is undefined behavior. A panicking
+/// handler is instead logged with its request id and answered with a
+/// `500 Internal Server Error` response, leaving the process able to serve later invocations.
+/// Lando's own errors returned by handlers are answered with a [Problem](struct.Problem.html),
+/// while any other error fails the invocation. Problems without an instance are given the
+/// API gateway request id, or Lambda's request id when invoked without one.
+pub(crate) fn call<F, R>(
+    func: F,
+    request: Request,
+    context: LambdaContext,
+) -> Result<response::GatewayResponse>
+where
+    F: FnOnce(Request, LambdaContext) -> StdResult<R, Box<StdError>>,
+    R: IntoResponse,
+{
+    let request_id = match request.request_context().request_id {
+        ref id if id.is_empty() => context.aws_request_id().to_owned(),
+        id => id,
+    };
+    let mut request = request.with_deadline(context.deadline());
+    request.extensions_mut().insert(context.clone());
+    // handlers are not reused after a panic so observing broken invariants is not a concern
+    let response = match panic::catch_unwind(AssertUnwindSafe(move || {
+        func(request, context).map(IntoResponse::into_response)
+    })) {
+        Ok(Ok(response)) => response,
+        Ok(Err(err)) => match problem::from_error(err.as_ref()) {
+            Some(problem) => {
+                if err.downcast_ref::<state::StateError>().is_some() {
+                    eprintln!("init error handling request {}: {}", request_id, err);
+                }
+                problem.into_response()
+            }
+            _ => return Err(err),
+        },
+        Err(payload) => {
+            eprintln!(
+                "handler panicked handling request {}: {}",
+                request_id,
+                panic_message(&payload)
+            );
+            Problem::new(http::StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+    let response = problem::with_instance(response, &request_id);
+    Ok(response::GatewayResponse::from(response))
+}
+
+/// Return the message a panic was raised with, if any
+fn panic_message(payload: &Box<Any + Send>) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<Any>")
+}
+
 /// A macro that exposes a Lambda function handler for AWS API gateway proxy event triggers.
 ///
 /// Lambda functions accept two arguments (the event, a [lando::Request](type.Request.html), and a context, a
@@ -281,7 +413,8 @@ where
 /// extendable with API gateway features, like accessing path and query string parameters, and
 /// more by importing [lando::RequestExt`](trait.RequestExt.html)
 ///
-/// The context argument is [same type](struct.LambdaContext.html) defined within the crowbar crate.
+/// The context argument is a [lando::LambdaContext](struct.LambdaContext.html), which may also be
+/// constructed when testing handlers.
 ///
 /// ```rust
 /// # #[macro_use] extern crate lando;
@@ -300,6 +433,25 @@ where
 /// # fn main() { }
 /// ```
 ///
+/// # Asynchronous handlers
+///
+/// `gateway!` invokes handlers synchronously and does not accept `async fn`s or closures
+/// returning futures directly. Adapt them with
+/// [executor::blocking](executor/fn.blocking.html), which drives their futures to completion.
+/// Functions attributed with `#[lando]` are adapted for you.
+///
+/// ```rust,ignore
+/// #[macro_use] extern crate lando;
+/// use lando::executor::blocking;
+/// use lando::{IntoResponse, LambdaContext, Request, Result};
+///
+/// async fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("👋")
+/// }
+///
+/// gateway!(blocking(handler));
+/// ```
+///
 /// # Export multiple lambda functions in one library
 ///
 /// You can export multiple functions in the same module with a format similar to a `match` expression:
@@ -358,10 +510,156 @@ macro_rules! gateway {
     };
 }
 
+/// A macro that exports the functions attributed with `#[lando]` in a crate
+///
+/// A crate may only define one Python module initializer, so all of its attributed functions
+/// are listed in a single `export!` call, which generates it.
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// use lando::{IntoResponse, LambdaContext, Request, Result};
+///
+/// #[lando]
+/// fn users(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("users")
+/// }
+///
+/// #[lando(name = "order_handler")]
+/// fn orders(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("orders")
+/// }
+///
+/// // exports libmycrate.users and libmycrate.order_handler
+/// export!(users, orders);
+/// # fn main() { }
+/// ```
+#[macro_export]
+macro_rules! export {
+    ($($handler:ident),*) => {
+        $crate::paste_item! {
+            gateway! { $([<__lando_name_ $handler>] => [<__lando_handler_ $handler>]),* }
+        }
+    };
+    ($($handler:ident,)*) => {
+        export!($($handler),*);
+    };
+}
+
+/// A macro that collects functions attributed with `#[lando(route = "...")]` into a
+/// [Router](router/struct.Router.html)
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// use lando::{IntoResponse, LambdaContext, Request, Result};
+///
+/// #[lando(route = "GET /health")]
+/// fn health(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   Ok("ok")
+/// }
+///
+/// gateway!("api" => |request, context| routes![health].handle(request, context));
+/// # fn main() { }
+/// ```
+#[macro_export]
+macro_rules! routes {
+    ($($handler:ident),*) => {
+        $crate::paste_expr! {
+            $crate::router::Router::new()$(.with([<__lando_route_ $handler>]()))*
+        }
+    };
+    ($($handler:ident,)*) => {
+        routes![$($handler),*]
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+
+    #[test]
+    fn panicking_handlers_do_not_take_down_later_invocations() {
+        let context = || LambdaContext::default().with_aws_request_id("abc-123");
+        let panicked = call(
+            |_, _| -> Result<()> { panic!("boom") },
+            Request::new(Body::Empty),
+            context(),
+        )
+        .expect("panic was not isolated");
+        assert_eq!(panicked.status_code, 500);
+        let panicked = call(
+            |_, _| -> Result<()> { panic!("boom {}", 2) },
+            Request::new(Body::Empty),
+            context(),
+        )
+        .expect("panic was not isolated");
+        assert_eq!(panicked.status_code, 500);
+        let response =
+            call(|_, _| Ok("ok"), Request::new(Body::Empty), context()).expect("handler failed");
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, Some(Body::from("ok")));
+    }
+
+    #[test]
+    fn lando_errors_are_problems() {
+        use failure::Fail;
+
+        let request = || {
+            Request::new(Body::Empty).with_request_context(request::RequestContext {
+                request_id: "req-1".into(),
+                ..request::RequestContext::default()
+            })
+        };
+        let context = || LambdaContext::default().with_aws_request_id("abc-123");
+        let rejected = call(
+            |_, _| -> Result<()> {
+                Err(Box::new(
+                    ext::PayloadError::BodyTooLarge { size: 2, limit: 1 }.compat(),
+                ))
+            },
+            request(),
+            context(),
+        )
+        .expect("payload error failed the invocation");
+        assert_eq!(rejected.status_code, 413);
+        let problem: serde_json::Value =
+            serde_json::from_slice(&rejected.body.unwrap_or_default()).expect("invalid problem");
+        assert_eq!(problem["instance"], "req-1");
+        let panicked = call(
+            |_, _| -> Result<()> { panic!("boom") },
+            Request::new(Body::Empty),
+            context(),
+        )
+        .expect("panic was not isolated");
+        assert_eq!(
+            panicked.body,
+            Some(Body::from(
+                r#"{"title":"Internal Server Error","status":500,"instance":"abc-123"}"#
+            ))
+        );
+    }
+
+    #[test]
+    fn other_errors_fail_invocations() {
+        let failed = call(
+            |_, _| -> Result<()> { Err("connection refused".into()) },
+            Request::new(Body::Empty),
+            LambdaContext::default(),
+        )
+        .expect_err("handler error was answered");
+        assert_eq!(failed.to_string(), "connection refused");
+    }
+
+    #[test]
+    fn panic_messages() {
+        let message = |payload| panic_message(&payload).to_owned();
+        assert_eq!(message(Box::new("boom") as Box<Any + Send>), "boom");
+        assert_eq!(
+            message(Box::new(String::from("boom")) as Box<Any + Send>),
+            "boom"
+        );
+        assert_eq!(message(Box::new(42) as Box<Any + Send>), "Box<Any>");
+    }
     #[test]
     fn json_into_response() {
         let response = json!({ "hello": "lambda"}).into_response();
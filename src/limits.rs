@@ -0,0 +1,195 @@
+//! Request body limits enforced before payloads are deserialized
+
+// Ours
+use ext::PayloadError;
+use middleware::Middleware;
+use problem::Problem;
+use {Body, IntoResponse, Request, Response, Result};
+
+/// Limits applied to request bodies when deserializing payloads with
+/// [RequestExt#payload](trait.RequestExt.html#tymethod.payload)
+///
+/// Each limit is optional. The default value imposes no limits.
+///
+/// `Limits` are also [Middleware](middleware/trait.Middleware.html). When applied,
+/// requests with bodies larger than `max_body_bytes` are rejected with a `413 Payload Too Large`
+/// [problem](struct.Problem.html) response before reaching your handler and the limits are
+/// applied to all payloads your handler deserializes.
+///
+/// ```rust
+/// # extern crate lando;
+/// use lando::{Body, Limits, PayloadError, RequestExt};
+/// use std::collections::HashMap;
+///
+/// let request = lando::http::Request::builder()
+///   .header("Content-Type", "application/json")
+///   .body(Body::from(r#"{"a":{"b":{"c":{}}}}"#))
+///   .unwrap();
+/// let limits = Limits {
+///   max_json_depth: Some(2),
+///   ..Limits::default()
+/// };
+/// assert!(
+///   match request.payload_with_limits::<HashMap<String, ()>>(&limits) {
+///     Err(PayloadError::JsonTooDeep { .. }) => true,
+///     _ => false
+///   }
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Maximum number of bytes of a request body
+    pub max_body_bytes: Option<usize>,
+    /// Maximum nesting depth of `application/json` arrays and objects
+    pub max_json_depth: Option<usize>,
+    /// Maximum number of `application/x-www-form-urlencoded` fields
+    pub max_form_fields: Option<usize>,
+}
+
+impl Limits {
+    pub(crate) fn check_body(&self, body: &[u8]) -> ::std::result::Result<(), PayloadError> {
+        match self.max_body_bytes {
+            Some(limit) if body.len() > limit => Err(PayloadError::BodyTooLarge {
+                size: body.len(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_json(&self, body: &[u8]) -> ::std::result::Result<(), PayloadError> {
+        match self.max_json_depth {
+            Some(limit) if json_depth_exceeds(body, limit) => {
+                Err(PayloadError::JsonTooDeep { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_form(&self, body: &[u8]) -> ::std::result::Result<(), PayloadError> {
+        match self.max_form_fields {
+            Some(limit) => {
+                let count = body
+                    .split(|b| *b == b'&')
+                    .filter(|field| !field.is_empty())
+                    .count();
+                if count > limit {
+                    Err(PayloadError::TooManyFormFields { count, limit })
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Scan json text for array and object nesting deeper than `limit`,
+/// ignoring brackets within strings
+fn json_depth_exceeds(json: &[u8], limit: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in json {
+        if in_string {
+            match *byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match *byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > limit {
+                    return true;
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    false
+}
+
+impl Middleware for Limits {
+    fn handle<N>(&self, mut request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        if let Err(err) = self.check_body(request.body()) {
+            return Ok(Problem::from(&err).with_request(&request).into_response());
+        }
+        request.extensions_mut().insert(*self);
+        next(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ext::RequestExt;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+    use middleware::wrap;
+    use std::collections::HashMap;
+
+    #[test]
+    fn detects_json_depth() {
+        assert!(!json_depth_exceeds(br#"{"a":[1,2]}"#, 2));
+        assert!(json_depth_exceeds(br#"{"a":[[1],2]}"#, 2));
+        assert!(!json_depth_exceeds(br#"{"a":"[[[[{{{{"}"#, 1));
+        assert!(!json_depth_exceeds(br#"{"a":"\"[[[[{{{{"}"#, 1));
+    }
+
+    #[test]
+    fn checks_form_fields() {
+        let limits = Limits {
+            max_form_fields: Some(2),
+            ..Limits::default()
+        };
+        assert!(limits.check_form(b"a=1&b=2").is_ok());
+        match limits.check_form(b"a=1&b=2&c=3") {
+            Err(PayloadError::TooManyFormFields { count: 3, limit: 2 }) => (),
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn middleware_rejects_large_bodies() {
+        let handler = wrap(
+            Limits {
+                max_body_bytes: Some(2),
+                ..Limits::default()
+            },
+            |_: Request, _: ()| Ok("unreachable"),
+        );
+        let response = handler(Request::new(Body::from("large")), ()).expect("handler failed");
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn middleware_applies_limits_to_payloads() {
+        let handler = wrap(
+            Limits {
+                max_json_depth: Some(1),
+                ..Limits::default()
+            },
+            |request: Request, _: ()| {
+                Ok(match request.payload::<HashMap<String, Vec<usize>>>() {
+                    Err(PayloadError::JsonTooDeep { .. }) => "too deep",
+                    _ => "ok",
+                })
+            },
+        );
+        let request = ::http::Request::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"a":[1]}"#))
+            .unwrap();
+        let response = handler(request, ()).expect("handler failed");
+        assert_eq!(response.body(), &Body::from("too deep"));
+    }
+}
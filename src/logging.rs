@@ -0,0 +1,416 @@
+//! Structured JSON logging
+//!
+//! Lambda forwards a function's output to CloudWatch Logs, where
+//! [Logs Insights](https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/AnalyzingLogData.html)
+//! discovers the fields of JSON log lines.
+//!
+//! [AccessLog](struct.AccessLog.html) middleware emits one JSON line per invocation describing
+//! the request and its response. While a request is handled, its fields are also attached to
+//! every line logged through the [log](https://docs.rs/log) facade once a
+//! [JsonLogger](struct.JsonLogger.html) is installed.
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! #[macro_use] extern crate log;
+//! use lando::logging::{AccessLog, JsonLogger};
+//! use lando::middleware::wrap;
+//! use lando::{IntoResponse, LambdaContext, Request, Result};
+//! use std::sync::Once;
+//!
+//! // loggers are installed once per process, which outlives invocations
+//! static LOGGER: Once = Once::new();
+//!
+//! fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   // {"level":"INFO","message":"handling","requestId":"...","path":"/", ...}
+//!   info!("handling");
+//!   Ok(())
+//! }
+//!
+//! gateway!(|request, context| {
+//!   LOGGER.call_once(|| {
+//!     JsonLogger::init(log::LevelFilter::Info).expect("failed to install logger")
+//!   });
+//!   wrap(AccessLog::new(), handler)(request, context)
+//! });
+//! # fn main() { }
+//! ```
+//!
+//! Access lines are written to stdout unless another destination is provided with
+//! [AccessLog#with_writer](struct.AccessLog.html#method.with_writer).
+//!
+//! Access lines include the following fields
+//!
+//! * `requestId` - the API gateway request id
+//! * `awsRequestId` - the Lambda invocation request id
+//! * `apiId` - the API gateway API id
+//! * `stage` - the API gateway stage
+//! * `method` - the request method
+//! * `path` - the request path
+//! * `status` - the response status
+//! * `latencyMs` - the time taken to handle the request, in milliseconds
+//! * `error` - the handler's error, or panic message, for failed requests
+
+// Std
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Third Party
+use log::{self, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::{Map, Value};
+
+// Ours
+use ext::RequestExt;
+use middleware::Middleware;
+use {panic_message, Body, LambdaContext, Request, Response, Result};
+
+thread_local! {
+    static FIELDS: RefCell<Map<String, Value>> = RefCell::new(Map::new());
+}
+
+/// Restores the previously scoped fields when dropped
+struct Scope {
+    previous: Map<String, Value>,
+}
+
+impl Scope {
+    fn enter(fields: Map<String, Value>) -> Self {
+        Scope {
+            previous: FIELDS.with(|scoped| mem::replace(&mut *scoped.borrow_mut(), fields)),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let previous = mem::replace(&mut self.previous, Map::new());
+        FIELDS.with(|scoped| *scoped.borrow_mut() = previous);
+    }
+}
+
+/// Return the fields scoped to the request being handled
+fn scoped_fields() -> Map<String, Value> {
+    FIELDS.with(|scoped| scoped.borrow().clone())
+}
+
+/// Return a log line with scoped fields followed by `fields`
+fn line(level: Level, target: &str, message: String, fields: Map<String, Value>) -> Value {
+    let mut line = Map::new();
+    line.insert("level".into(), level.to_string().into());
+    line.insert("target".into(), target.into());
+    line.insert("message".into(), message.into());
+    line.extend(scoped_fields());
+    line.extend(fields);
+    Value::Object(line)
+}
+
+fn emit<W>(writer: &mut W, line: &Value)
+where
+    W: Write + ?Sized,
+{
+    // there is nowhere left to report failures to write logs
+    let _ = writer.write_all(format!("{}\n", line).as_bytes());
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + f64::from(duration.subsec_nanos()) / 1_000_000.0
+}
+
+/// Return the fields describing a request
+fn request_fields(request: &Request) -> Map<String, Value> {
+    let context = request.request_context();
+    let mut fields = Map::new();
+    fields.insert("requestId".into(), context.request_id.into());
+    if let Some(context) = request.extensions().get::<LambdaContext>() {
+        fields.insert("awsRequestId".into(), context.aws_request_id().into());
+    }
+    fields.insert("apiId".into(), context.api_id.into());
+    fields.insert("stage".into(), context.stage.into());
+    fields.insert("method".into(), request.method().as_str().into());
+    fields.insert("path".into(), request.uri().path().into());
+    fields
+}
+
+/// Middleware emitting a JSON access log line for each request
+#[derive(Clone)]
+pub struct AccessLog {
+    writer: Arc<Mutex<Write + Send>>,
+}
+
+impl AccessLog {
+    /// Return middleware writing access lines to stdout
+    pub fn new() -> Self {
+        AccessLog {
+            writer: Arc::new(Mutex::new(io::stdout())),
+        }
+    }
+
+    /// Write access lines to `writer` rather than stdout
+    pub fn with_writer<W>(mut self, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.writer = Arc::new(Mutex::new(writer));
+        self
+    }
+
+    fn record(
+        mut fields: Map<String, Value>,
+        result: &Result<Response<Body>>,
+        latency: Duration,
+    ) -> Value {
+        let status = match result {
+            Ok(response) => response.status().as_u16(),
+            Err(err) => {
+                fields.insert("error".into(), err.to_string().into());
+                500
+            }
+        };
+        let message = format!(
+            "{} {} {}",
+            fields["method"].as_str().unwrap_or_default(),
+            fields["path"].as_str().unwrap_or_default(),
+            status
+        );
+        fields.insert("status".into(), status.into());
+        fields.insert("latencyMs".into(), millis(latency).into());
+        let level = if status >= 500 {
+            Level::Error
+        } else {
+            Level::Info
+        };
+        line(level, "lando::access", message, fields)
+    }
+}
+
+impl Middleware for AccessLog {
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        let start = Instant::now();
+        let fields = request_fields(&request);
+        // panics are recorded as failures before resuming, so every request is logged
+        let outcome = {
+            let _scope = Scope::enter(fields.clone());
+            panic::catch_unwind(AssertUnwindSafe(move || next(request)))
+        };
+        let line = match outcome {
+            Ok(ref result) => AccessLog::record(fields, result, start.elapsed()),
+            Err(ref payload) => {
+                let panicked = Err(format!("panicked: {}", panic_message(payload)).into());
+                AccessLog::record(fields, &panicked, start.elapsed())
+            }
+        };
+        {
+            let mut writer = self
+                .writer
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            emit(&mut *writer, &line);
+        }
+        match outcome {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        AccessLog::new()
+    }
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccessLog").finish()
+    }
+}
+
+/// A `log` facade logger writing JSON lines, including fields scoped to the request
+/// being handled, to stdout
+#[derive(Debug)]
+pub struct JsonLogger {
+    level: LevelFilter,
+}
+
+impl JsonLogger {
+    /// Install a logger for records at or above `level`. Fails if a logger was already
+    /// installed, so install it once per process rather than once per invocation
+    pub fn init(level: LevelFilter) -> ::std::result::Result<(), SetLoggerError> {
+        log::set_boxed_logger(Box::new(JsonLogger { level }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    fn record(record: &Record) -> Value {
+        let mut fields = Map::new();
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            fields.insert("location".into(), format!("{}:{}", file, line).into());
+        }
+        line(
+            record.level(),
+            record.target(),
+            record.args().to_string(),
+            fields,
+        )
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            emit(&mut io::stdout().lock(), &JsonLogger::record(record))
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use middleware::wrap;
+    use request::RequestContext;
+    use serde_json::json;
+    use testing::RequestBuilder;
+
+    /// A writer capturing access lines for assertions
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Captured {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .expect("invalid utf8")
+                .lines()
+                .map(|line| serde_json::from_str(line).expect("invalid json line"))
+                .collect()
+        }
+    }
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request() -> Request {
+        let mut context = RequestContext::default();
+        context.request_id = "req-1".into();
+        context.api_id = "api-1".into();
+        context.stage = "prod".into();
+        let mut request = RequestBuilder::new()
+            .method("POST")
+            .uri("https://example.com/orders")
+            .request_context(context)
+            .body(())
+            .expect("failed to build request");
+        request
+            .extensions_mut()
+            .insert(LambdaContext::default().with_aws_request_id("aws-1"));
+        request
+    }
+
+    #[test]
+    fn records_access() {
+        let fields = request_fields(&request());
+        let response = Ok(Response::new(Body::Empty));
+        let record = AccessLog::record(fields.clone(), &response, Duration::from_millis(12));
+        assert_eq!(
+            record,
+            json!({
+                "level": "INFO",
+                "target": "lando::access",
+                "message": "POST /orders 200",
+                "requestId": "req-1",
+                "awsRequestId": "aws-1",
+                "apiId": "api-1",
+                "stage": "prod",
+                "method": "POST",
+                "path": "/orders",
+                "status": 200,
+                "latencyMs": 12.0
+            })
+        );
+        let record = AccessLog::record(fields, &Err("boom".into()), Duration::from_millis(1));
+        assert_eq!(record["level"], "ERROR");
+        assert_eq!(record["status"], 500);
+        assert_eq!(record["error"], "boom");
+    }
+
+    #[test]
+    fn writes_access_lines() {
+        let captured = Captured::default();
+        let access = AccessLog::new().with_writer(captured.clone());
+        wrap(&access, |_: Request, _: ()| Ok("ok"))(request(), ()).expect("handler failed");
+        wrap(&access, |_: Request, _: ()| Err::<(), _>("boom".into()))(request(), ())
+            .expect_err("handler succeeded");
+        let lines = captured.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["message"], "POST /orders 200");
+        assert_eq!(lines[0]["requestId"], "req-1");
+        assert_eq!(lines[0]["awsRequestId"], "aws-1");
+        assert!(lines[0]["latencyMs"].is_f64());
+        assert_eq!(lines[1]["level"], "ERROR");
+        assert_eq!(lines[1]["status"], 500);
+        assert_eq!(lines[1]["error"], "boom");
+    }
+
+    #[test]
+    fn writes_access_lines_for_panics() {
+        let captured = Captured::default();
+        let access = AccessLog::new().with_writer(captured.clone());
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            wrap(&access, |_: Request, _: ()| -> Result<()> {
+                panic!("boom")
+            })(request(), ())
+        }));
+        assert!(panicked.is_err(), "panic was not resumed");
+        let lines = captured.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "ERROR");
+        assert_eq!(lines[0]["status"], 500);
+        assert_eq!(lines[0]["error"], "panicked: boom");
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn scopes_fields_to_requests() {
+        let captured = Captured::default();
+        let access = AccessLog::new().with_writer(captured.clone());
+        let response = wrap(access, |_: Request, _: ()| {
+            let line = JsonLogger::record(
+                &Record::builder()
+                    .args(format_args!("handling"))
+                    .level(Level::Warn)
+                    .target("orders")
+                    .build(),
+            );
+            assert_eq!(line["message"], "handling");
+            assert_eq!(line["level"], "WARN");
+            assert_eq!(line["requestId"], "req-1");
+            assert_eq!(line["path"], "/orders");
+            Ok("ok")
+        })(request(), ())
+        .expect("handler failed");
+        assert_eq!(response.body(), &Body::from("ok"));
+        assert!(scoped_fields().is_empty());
+        assert_eq!(captured.lines().len(), 1);
+    }
+}
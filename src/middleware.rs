@@ -0,0 +1,111 @@
+//! Middleware for composing behavior around handlers
+//!
+//! A [Middleware](trait.Middleware.html) receives a request before a handler does and
+//! decides whether, and with which request, to invoke the `next` handler in the chain. It may
+//! also inspect or transform the handler's response.
+//!
+//! Wrap handlers with middleware using [wrap](fn.wrap.html)
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! use lando::middleware::wrap;
+//! use lando::{LambdaContext, Limits, Request, Result, IntoResponse};
+//!
+//! fn handler(
+//!   request: Request,
+//!   _: LambdaContext
+//! ) -> Result<impl IntoResponse> {
+//!   Ok(format!("received {} bytes", request.body().len()))
+//! }
+//!
+//! gateway!(wrap(
+//!   Limits {
+//!     max_body_bytes: Some(1024),
+//!     ..Limits::default()
+//!   },
+//!   handler
+//! ));
+//! # fn main() { }
+//! ```
+
+// Ours
+use {Body, IntoResponse, Request, Response, Result};
+
+/// Behavior applied around a handler
+pub trait Middleware {
+    /// Handle a request, typically by invoking `next` with it
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>;
+}
+
+impl<'a, M> Middleware for &'a M
+where
+    M: Middleware,
+{
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        (*self).handle(request, next)
+    }
+}
+
+/// Return a handler which applies `middleware` around `handler`
+///
+/// Wrapped handlers may themselves be wrapped, the outermost middleware
+/// being the first to see a request.
+pub fn wrap<M, F, C, R>(
+    middleware: M,
+    handler: F,
+) -> impl FnOnce(Request, C) -> Result<Response<Body>>
+where
+    M: Middleware,
+    F: FnOnce(Request, C) -> Result<R>,
+    R: IntoResponse,
+{
+    move |request, context| {
+        middleware.handle(request, move |request| {
+            handler(request, context).map(IntoResponse::into_response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderValue;
+
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+        where
+            N: FnOnce(Request) -> Result<Response<Body>>,
+        {
+            let mut response = next(request)?;
+            response
+                .headers_mut()
+                .append("x-tag", HeaderValue::from_static(self.0));
+            Ok(response)
+        }
+    }
+
+    #[test]
+    fn wrap_applies_middleware_outside_in() {
+        let handler = wrap(
+            Tag("outer"),
+            wrap(Tag("inner"), |_: Request, _: ()| Ok("hello")),
+        );
+        let response = handler(Request::new(Body::Empty), ()).expect("handler failed");
+        assert_eq!(
+            response
+                .headers()
+                .get_all("x-tag")
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["inner", "outer"]
+        );
+        assert_eq!(response.body(), &Body::from("hello"));
+    }
+}
@@ -0,0 +1,358 @@
+//! Content negotiation driven by `Accept` headers
+
+// Std
+use std::cmp::Ordering;
+
+// Third Party
+use http::header::{HeaderValue, ACCEPT, CONTENT_TYPE, VARY};
+use http::StatusCode;
+use serde::Serialize;
+
+// Ours
+use super::{Body, IntoResponse, Request, Response};
+use format;
+
+/// A single `Accept` header media range, i.e. `text/*;q=0.8`
+#[derive(Debug, PartialEq)]
+struct MediaRange<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    quality: f32,
+}
+
+impl<'a> MediaRange<'a> {
+    fn parse(value: &'a str) -> Option<MediaRange<'a>> {
+        let mut params = value.split(';');
+        let mut essence = params.next()?.trim().splitn(2, '/');
+        let (kind, subtype) = match (essence.next(), essence.next()) {
+            (Some(kind), Some(subtype)) if !kind.is_empty() && !subtype.is_empty() => {
+                (kind.trim(), subtype.trim())
+            }
+            _ => return None,
+        };
+        let quality = params
+            .filter_map(|param| {
+                let mut pair = param.splitn(2, '=');
+                match (pair.next(), pair.next()) {
+                    (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("q") => {
+                        value.trim().parse::<f32>().ok()
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+        Some(MediaRange {
+            kind,
+            subtype,
+            quality: quality.max(0.0).min(1.0),
+        })
+    }
+
+    /// Return how specifically this range matches a media type, if at all
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        let mut parts = essence.splitn(2, '/');
+        let (kind, subtype) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        match (self.kind, self.subtype) {
+            ("*", "*") => Some(0),
+            (k, "*") if k.eq_ignore_ascii_case(kind) => Some(1),
+            (k, s) if k.eq_ignore_ascii_case(kind) && s.eq_ignore_ascii_case(subtype) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Select the best of the `offered` media types for an `Accept` header value
+///
+/// Each offered type is weighed by the quality of the most specific media range matching it.
+/// Ties are broken by the order of `offered`. A missing or empty `Accept` header accepts anything.
+pub(crate) fn negotiate<'a>(accept: Option<&str>, offered: &[&'a str]) -> Option<&'a str> {
+    let ranges = match accept {
+        Some(accept) if !accept.trim().is_empty() => accept
+            .split(',')
+            .filter_map(MediaRange::parse)
+            .collect::<Vec<_>>(),
+        _ => return offered.first().cloned(),
+    };
+    let mut best: Option<(&'a str, f32)> = None;
+    for offer in offered {
+        let quality = ranges
+            .iter()
+            .filter_map(|range| range.specificity(offer).map(|s| (s, range.quality)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, quality)| quality)
+            .unwrap_or(0.0);
+        if quality <= 0.0 {
+            continue;
+        }
+        best = match best {
+            Some((_, current)) if current.partial_cmp(&quality) != Some(Ordering::Less) => best,
+            _ => Some((offer, quality)),
+        };
+    }
+    best.map(|(offer, _)| offer)
+}
+
+/// Renders a value as the body of a response
+enum Renderer<T> {
+    /// One of the formats lando serializes values to
+    Serialized(&'static str),
+    /// A renderer provided with `Negotiated#with_renderer`
+    Custom(&'static str, Box<Fn(&T) -> Result<Body, String>>),
+}
+
+impl<T> Renderer<T> {
+    fn media_type(&self) -> &'static str {
+        match self {
+            Renderer::Serialized(media_type) | Renderer::Custom(media_type, _) => media_type,
+        }
+    }
+}
+
+/// A serializable value rendered in the format a client prefers
+///
+/// The format is selected from the request's `Accept` header among the media types
+/// lando knows how to serialize to: `application/json`, as well as
+/// `application/xml`, `application/yaml`, `application/msgpack` and `application/cbor` when their
+/// respective `xml`, `yaml`, `msgpack` and `cbor` cargo features are enabled.
+/// Other media types, like `text/csv` or `text/html`, may be offered by providing a renderer
+/// for them with [with_renderer](#method.with_renderer).
+/// If none are acceptable, a `406 Not Acceptable` response is returned.
+///
+/// # example
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// # #[macro_use] extern crate serde_derive;
+/// use lando::Negotiated;
+///
+/// #[derive(Serialize)]
+/// struct Report {
+///   total: usize
+/// }
+///
+/// gateway!(|request, _| {
+///   Ok(
+///     Negotiated::new(&request, Report { total: 42 })
+///       .with_renderer("text/csv", |report| Ok(format!("total\n{}\n", report.total).into()))
+///       .with_renderer("text/html; charset=utf-8", |report| {
+///         Ok(format!("<p>total: {}</p>", report.total).into())
+///       }),
+///   )
+/// });
+/// # fn main() { }
+/// ```
+pub struct Negotiated<T> {
+    accept: Option<String>,
+    renderers: Vec<Renderer<T>>,
+    value: T,
+}
+
+impl<T> Negotiated<T>
+where
+    T: Serialize,
+{
+    /// Return a new `Negotiated` value for a given request
+    pub fn new(request: &Request, value: T) -> Self {
+        Negotiated {
+            accept: accept(request).map(String::from),
+            renderers: format::serializable()
+                .into_iter()
+                .map(Renderer::Serialized)
+                .collect(),
+            value,
+        }
+    }
+
+    /// Offer the value rendered as `media_type` by `render`, replacing any other renderer
+    /// for that media type
+    ///
+    /// Media types may include parameters, i.e. `text/html; charset=utf-8`, which are
+    /// ignored when negotiating but included in the response's `Content-Type`. Offers are
+    /// preferred in the order they are made, after lando's own formats. Values which fail to
+    /// render result in a `500 Internal Server Error` response.
+    pub fn with_renderer<F>(mut self, media_type: &'static str, render: F) -> Self
+    where
+        F: Fn(&T) -> Result<Body, String> + 'static,
+    {
+        self.renderers
+            .retain(|renderer| renderer.media_type() != media_type);
+        self.renderers
+            .push(Renderer::Custom(media_type, Box::new(render)));
+        self
+    }
+
+    /// Return the media type selected for this value, if any
+    pub fn media_type(&self) -> Option<&'static str> {
+        self.selected().map(Renderer::media_type)
+    }
+
+    fn selected(&self) -> Option<&Renderer<T>> {
+        let offered = self
+            .renderers
+            .iter()
+            .map(Renderer::media_type)
+            .collect::<Vec<_>>();
+        let media_type = negotiate(self.accept.as_ref().map(String::as_str), &offered)?;
+        self.renderers
+            .iter()
+            .find(|renderer| renderer.media_type() == media_type)
+    }
+}
+
+impl<T> IntoResponse for Negotiated<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<Body> {
+        let mut response = match self.selected() {
+            Some(Renderer::Serialized(media_type)) => format::respond(media_type, &self.value),
+            Some(Renderer::Custom(media_type, render)) => {
+                format::respond_with(media_type, render(&self.value))
+            }
+            None => {
+                let mut response = Response::new(Body::Empty);
+                *response.status_mut() = StatusCode::NOT_ACCEPTABLE;
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("text/plain; charset=utf-8"),
+                );
+                *response.body_mut() = Body::from(format!(
+                    "acceptable media types: {}",
+                    self.renderers
+                        .iter()
+                        .map(Renderer::media_type)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                response
+            }
+        };
+        response
+            .headers_mut()
+            .insert(VARY, HeaderValue::from_static("Accept"));
+        response
+    }
+}
+
+/// Return the `Accept` header of a request as a string, if one was provided
+pub(crate) fn accept(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parses_media_ranges() {
+        assert_eq!(
+            MediaRange::parse("text/*; q=0.5"),
+            Some(MediaRange {
+                kind: "text",
+                subtype: "*",
+                quality: 0.5
+            })
+        );
+        assert_eq!(MediaRange::parse("text"), None);
+    }
+
+    #[test]
+    fn negotiate_without_accept_prefers_first_offer() {
+        assert_eq!(
+            negotiate(None, &["text/csv", "application/json"]),
+            Some("text/csv")
+        );
+    }
+
+    #[test]
+    fn negotiate_respects_quality() {
+        assert_eq!(
+            negotiate(
+                Some("application/json;q=0.5, text/csv"),
+                &["application/json", "text/csv"]
+            ),
+            Some("text/csv")
+        );
+    }
+
+    #[test]
+    fn negotiate_prefers_specific_ranges() {
+        assert_eq!(
+            negotiate(
+                Some("text/*;q=0.9, text/html;q=0, */*;q=0.1"),
+                &["text/html", "text/csv", "application/json"]
+            ),
+            Some("text/csv")
+        );
+    }
+
+    #[test]
+    fn negotiate_without_match() {
+        assert_eq!(negotiate(Some("image/png"), &["application/json"]), None);
+    }
+
+    fn request(accept: &str) -> Request {
+        ::http::Request::builder()
+            .header(ACCEPT, accept)
+            .body(Body::Empty)
+            .unwrap()
+    }
+
+    fn negotiated(accept: &str) -> Negotiated<HashMap<&'static str, &'static str>> {
+        let mut value = HashMap::new();
+        value.insert("foo", "bar");
+        Negotiated::new(&request(accept), value)
+            .with_renderer("text/csv", |value| {
+                Ok(Body::from(format!("foo\n{}\n", value["foo"])))
+            })
+            .with_renderer("text/html; charset=utf-8", |value| {
+                Ok(Body::from(format!("<p>{}</p>", value["foo"])))
+            })
+    }
+
+    #[test]
+    fn negotiated_renders_preferred_format() {
+        let response = negotiated("application/json").into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.body(), &Body::from(r#"{"foo":"bar"}"#));
+        let response = negotiated("text/csv;q=0.5, text/html").into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(response.body(), &Body::from("<p>bar</p>"));
+        let response = negotiated("text/*;q=0.9, application/json;q=0.1").into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+        assert_eq!(response.body(), &Body::from("foo\nbar\n"));
+    }
+
+    #[test]
+    fn negotiated_renderers_replace_others() {
+        let response = Negotiated::new(&request("application/json"), ())
+            .with_renderer("application/json", |_| Ok(Body::from("{}")))
+            .into_response();
+        assert_eq!(response.body(), &Body::from("{}"));
+        let response = Negotiated::new(&request("text/csv"), ())
+            .with_renderer("text/csv", |_| Err("no rows".into()))
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn negotiated_does_not_render_forms() {
+        let negotiated = negotiated("application/x-www-form-urlencoded");
+        assert_eq!(negotiated.media_type(), None);
+        let response = negotiated.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+        let body = String::from_utf8_lossy(response.body());
+        assert!(body.starts_with("acceptable media types: application/json, "));
+        assert!(body.ends_with(", text/csv, text/html; charset=utf-8"));
+        assert!(!body.contains("x-www-form-urlencoded"));
+    }
+}
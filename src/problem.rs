@@ -0,0 +1,393 @@
+//! [RFC 7807](https://tools.ietf.org/html/rfc7807) problem details for http APIs
+
+// Std
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+
+// Third Party
+use failure::Compat;
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+use serde_json::{self, Value};
+
+// Ours
+use ext::{PayloadError, RequestExt};
+use state::StateError;
+use timeout::DeadlineExceeded;
+use validate::ValidationErrors;
+use {Body, IntoResponse, Request, Response};
+
+fn about_blank() -> String {
+    "about:blank".into()
+}
+
+fn is_about_blank(value: &str) -> bool {
+    value == "about:blank"
+}
+
+/// An `application/problem+json` error response
+///
+/// Problems describe errors in a machine readable way, with a `type` uri identifying the kind
+/// of problem, a human readable `title` and `detail` and an `instance` identifying the specific
+/// occurrence of the problem. Additional members may be provided as extensions.
+///
+/// Lando errors like [PayloadError](enum.PayloadError.html) convert into problems.
+/// Lando's own errors returned by handlers, including `PayloadError`s returned with
+/// `err.compat()`, are answered with their corresponding problem. Any other error returned
+/// by a handler still fails the invocation, so Lambda's error metrics and retries apply.
+///
+/// Problems answering a request are given the request's id as their `instance`
+/// unless they were rendered with one.
+///
+/// # example
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// # #[macro_use] extern crate serde_derive;
+/// use lando::http::StatusCode;
+/// use lando::{IntoResponse, Problem, RequestExt};
+///
+/// #[derive(Deserialize)]
+/// struct Order {
+///   quantity: usize
+/// }
+///
+/// gateway!(|request, _| {
+///   Ok(match request.payload::<Order>() {
+///     Ok(Some(order)) => format!("ordered {}", order.quantity).into_response(),
+///     Ok(None) => Problem::new(StatusCode::BAD_REQUEST)
+///        .with_detail("an order is required")
+///        .into_response(),
+///     Err(err) => Problem::from(err).into_response()
+///   })
+/// });
+/// # fn main() { }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    #[serde(
+        rename = "type",
+        default = "about_blank",
+        skip_serializing_if = "is_about_blank"
+    )]
+    problem_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    #[serde(flatten)]
+    extensions: BTreeMap<String, Value>,
+}
+
+impl Problem {
+    /// Return a new problem for a given status code, titled with the status's canonical reason
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            problem_type: about_blank(),
+            title: status.canonical_reason().map(String::from),
+            status: Some(status.as_u16()),
+            detail: None,
+            instance: None,
+            extensions: BTreeMap::new(),
+        }
+    }
+
+    /// Set a uri reference identifying the type of problem
+    pub fn with_type<T>(mut self, problem_type: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.problem_type = problem_type.into();
+        self
+    }
+
+    /// Set a short, human readable summary of the type of problem
+    pub fn with_title<T>(mut self, title: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set a human readable explanation specific to this occurrence of the problem
+    pub fn with_detail<D>(mut self, detail: D) -> Self
+    where
+        D: Into<String>,
+    {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Set a uri reference identifying this specific occurrence of the problem
+    pub fn with_instance<I>(mut self, instance: I) -> Self
+    where
+        I: Into<String>,
+    {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Set the instance to the API gateway request id of a request, when available
+    ///
+    /// Problems answering a request are given its id when rendered, so this is only
+    /// needed for problems used for other purposes
+    pub fn with_request(self, request: &Request) -> Self {
+        let request_id = request.request_context().request_id;
+        if request_id.is_empty() {
+            self
+        } else {
+            self.with_instance(request_id)
+        }
+    }
+
+    /// Add an extension member
+    pub fn with_extension<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.extensions.insert(key.into(), value.into());
+        self
+    }
+
+    /// Return the uri reference identifying the type of problem
+    pub fn problem_type(&self) -> &str {
+        &self.problem_type
+    }
+
+    /// Return the summary of the type of problem
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_ref().map(String::as_str)
+    }
+
+    /// Return the status code of the problem. Problems without a valid status
+    /// are reported as `500 Internal Server Error`
+    pub fn status(&self) -> StatusCode {
+        self.status
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Return the explanation of this occurrence of the problem
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_ref().map(String::as_str)
+    }
+
+    /// Return the uri reference identifying this occurrence of the problem
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_ref().map(String::as_str)
+    }
+
+    /// Return an extension member
+    pub fn extension(&self, key: &str) -> Option<&Value> {
+        self.extensions.get(key)
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response<Body> {
+        let mut response = Response::new(Body::Empty);
+        *response.status_mut() = self.status();
+        if let Ok(json) = serde_json::to_string(&self) {
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            );
+            *response.body_mut() = Body::from(json);
+        }
+        // retained so the instance may be filled in once the request is known
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Return a problem answering a handler's error, if it is one of lando's own
+pub(crate) fn from_error(err: &(StdError + 'static)) -> Option<Problem> {
+    if let Some(err) = err.downcast_ref::<Compat<PayloadError>>() {
+        Some(Problem::from(err.get_ref()))
+    } else if let Some(errors) = err.downcast_ref::<ValidationErrors>() {
+        Some(Problem::from(errors))
+    } else if err.downcast_ref::<StateError>().is_some() {
+        Some(Problem::new(StatusCode::SERVICE_UNAVAILABLE))
+    } else if err.downcast_ref::<DeadlineExceeded>().is_some() {
+        Some(
+            Problem::new(StatusCode::GATEWAY_TIMEOUT)
+                .with_detail("the request could not be completed in time"),
+        )
+    } else {
+        None
+    }
+}
+
+/// Set the instance of a problem response rendered without one
+pub(crate) fn with_instance(mut response: Response<Body>, instance: &str) -> Response<Body> {
+    let problem = match response.extensions_mut().remove::<Problem>() {
+        Some(problem) => problem,
+        _ => return response,
+    };
+    let is_problem = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map_or(false, |value| value == "application/problem+json");
+    if problem.instance.is_none() && !instance.is_empty() && is_problem {
+        // headers and status may have been amended by middleware so only the body is replaced
+        *response.body_mut() = problem.with_instance(instance).into_response().into_body();
+    }
+    response
+}
+
+impl<'a> From<&'a PayloadError> for Problem {
+    fn from(err: &'a PayloadError) -> Self {
+        let status = match err {
+            PayloadError::BodyTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            PayloadError::Invalid(errors) => return Problem::from(errors),
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let mut problem = Problem::new(status).with_detail(err.to_string());
+        if let Some(location) = err.location() {
+            if !location.path().is_empty() {
+                problem = problem.with_extension("path", location.path());
+            }
+            if let Some(line) = location.line() {
+                problem = problem.with_extension("line", line);
+            }
+            if let Some(column) = location.column() {
+                problem = problem.with_extension("column", column);
+            }
+            if let Some(expected) = location.expected() {
+                problem = problem.with_extension("expected", expected);
+            }
+        }
+        problem
+    }
+}
+
+impl From<PayloadError> for Problem {
+    fn from(err: PayloadError) -> Self {
+        Problem::from(&err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::Fail;
+    use http::HeaderMap;
+    use request::{GatewayRequest, RequestContext};
+    use serde_json::{self, json};
+    use std::collections::HashMap;
+
+    #[test]
+    fn serializes_members() {
+        let problem = Problem::new(StatusCode::FORBIDDEN)
+            .with_type("https://example.com/probs/out-of-credit")
+            .with_detail("Your current balance is 30, but that costs 50.")
+            .with_instance("/account/12345/msgs/abc")
+            .with_extension("balance", 30);
+        assert_eq!(
+            serde_json::to_value(&problem).expect("failed to serialize problem"),
+            json!({
+                "type": "https://example.com/probs/out-of-credit",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "Your current balance is 30, but that costs 50.",
+                "instance": "/account/12345/msgs/abc",
+                "balance": 30
+            })
+        );
+    }
+
+    #[test]
+    fn omits_default_type() {
+        assert_eq!(
+            serde_json::to_string(&Problem::new(StatusCode::NOT_FOUND))
+                .expect("failed to serialize problem"),
+            r#"{"title":"Not Found","status":404}"#
+        );
+    }
+
+    #[test]
+    fn into_response() {
+        let response = Problem::new(StatusCode::CONFLICT).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+    }
+
+    #[test]
+    fn payload_errors_are_bad_requests() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Host", "www.rust-lang.org".parse().unwrap());
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let request = Request::from(GatewayRequest {
+            path: "/foo".into(),
+            headers,
+            request_context: RequestContext {
+                request_id: "abc-123".into(),
+                ..RequestContext::default()
+            },
+            body: Some(r#"{"quantity": "two"}"#.into()),
+            ..GatewayRequest::default()
+        });
+        let err = request
+            .payload::<HashMap<String, usize>>()
+            .expect_err("expected payload error");
+        let problem = Problem::from(err).with_request(&request);
+        assert_eq!(problem.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(problem.instance(), Some("abc-123"));
+        assert_eq!(
+            problem.detail(),
+            Some(
+                "failed to parse payload field `quantity` from application/json: \
+                 invalid type: string \"two\", expected usize at line 1 column 18"
+            )
+        );
+        assert_eq!(problem.extension("path"), Some(&json!("quantity")));
+        assert_eq!(problem.extension("expected"), Some(&json!("usize")));
+    }
+
+    #[test]
+    fn fills_in_missing_instances() {
+        let response = with_instance(
+            Problem::new(StatusCode::CONFLICT).into_response(),
+            "abc-123",
+        );
+        assert_eq!(
+            response.body(),
+            &Body::from(r#"{"title":"Conflict","status":409,"instance":"abc-123"}"#)
+        );
+        let response = with_instance(
+            Problem::new(StatusCode::CONFLICT)
+                .with_instance("/orders/1")
+                .into_response(),
+            "abc-123",
+        );
+        assert_eq!(
+            response.body(),
+            &Body::from(r#"{"title":"Conflict","status":409,"instance":"/orders/1"}"#)
+        );
+        let response = with_instance("ok".into_response(), "abc-123");
+        assert_eq!(response.body(), &Body::from("ok"));
+    }
+
+    #[test]
+    fn errors_convert_into_problems() {
+        let err: Box<StdError> = "connection refused".into();
+        assert_eq!(from_error(err.as_ref()), None);
+        let err: Box<StdError> =
+            Box::new(PayloadError::BodyTooLarge { size: 2, limit: 1 }.compat());
+        assert_eq!(
+            from_error(err.as_ref()).map(|problem| problem.status()),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+        let err: Box<StdError> = Box::new(DeadlineExceeded);
+        assert_eq!(
+            from_error(err.as_ref()).map(|problem| problem.status()),
+            Some(StatusCode::GATEWAY_TIMEOUT)
+        );
+    }
+}
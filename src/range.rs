@@ -0,0 +1,315 @@
+//! Support for serving partial content with http `Range` requests
+
+// Std
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Third Party
+use bytes::{Bytes, BytesMut};
+use http::header::{HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use http::{Response, StatusCode};
+
+// Ours
+use body::Body;
+
+/// An inclusive, resolved range of byte offsets within a body
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct ByteRange {
+    start: usize,
+    end: usize,
+}
+
+impl ByteRange {
+    fn content_range(&self, total: usize) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// The outcome of evaluating a `Range` header against a body of known length
+#[derive(Debug, PartialEq)]
+enum Ranges {
+    /// No (valid) range was requested. The full body should be returned
+    Full,
+    /// One or more satisfiable ranges were requested
+    Partial(Vec<ByteRange>),
+    /// Ranges were requested but none overlap the body
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header value for a body of `len` bytes
+///
+/// Per [RFC 7233](https://tools.ietf.org/html/rfc7233#section-3.1) syntactically
+/// invalid values and unsupported range units are ignored
+fn parse(value: &str, len: usize) -> Ranges {
+    let mut parts = value.splitn(2, '=');
+    let (unit, specs) = match (parts.next(), parts.next()) {
+        (Some(unit), Some(specs)) => (unit.trim(), specs),
+        _ => return Ranges::Full,
+    };
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return Ranges::Full;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut bounds = spec.splitn(2, '-');
+        let (first, last) = match (bounds.next(), bounds.next()) {
+            (Some(first), Some(last)) => (first.trim(), last.trim()),
+            _ => return Ranges::Full,
+        };
+        let range = if first.is_empty() {
+            // suffix range, the last n bytes
+            let suffix = match last.parse::<usize>() {
+                Ok(suffix) => suffix,
+                _ => return Ranges::Full,
+            };
+            if suffix == 0 || len == 0 {
+                None
+            } else {
+                Some(ByteRange {
+                    start: len.saturating_sub(suffix),
+                    end: len - 1,
+                })
+            }
+        } else {
+            let start = match first.parse::<usize>() {
+                Ok(start) => start,
+                _ => return Ranges::Full,
+            };
+            let end = if last.is_empty() {
+                None
+            } else {
+                match last.parse::<usize>() {
+                    Ok(end) if end >= start => Some(end),
+                    _ => return Ranges::Full,
+                }
+            };
+            if start >= len {
+                None
+            } else {
+                Some(ByteRange {
+                    start,
+                    end: end.map(|end| end.min(len - 1)).unwrap_or(len - 1),
+                })
+            }
+        };
+        ranges.extend(range);
+    }
+    if ranges.is_empty() {
+        // a header with at least one spec, none of which overlap the body
+        if specs.split(',').any(|s| !s.trim().is_empty()) {
+            return Ranges::Unsatisfiable;
+        }
+        return Ranges::Full;
+    }
+    Ranges::Partial(ranges)
+}
+
+fn default_content_type(body: &Body) -> &'static str {
+    match body {
+        Body::Text(_) => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn boundary(data: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    format!("lando-{:016x}", hasher.finish())
+}
+
+/// Return a response for `body` that honors any `Range` header provided in `request_headers`
+///
+/// * Requests without a `Range` header, or with one lando does not understand, get a `200 OK`
+///   response with the full body
+/// * Requests for a single satisfiable range get a `206 Partial Content` response with a `Content-Range` header
+/// * Requests for multiple satisfiable ranges get a `206 Partial Content` response with a
+///   `multipart/byteranges` body
+/// * Requests for ranges that do not overlap with the body get a `416 Range Not Satisfiable` response
+///
+/// Partial responses are always `Body::Binary` as ranges may not fall on character boundaries.
+/// The content type defaults to `text/plain; charset=utf-8` for `Body::Text` and `application/octet-stream`
+/// otherwise. Use [ranged_with_content_type](fn.ranged_with_content_type.html) to provide your own.
+///
+/// # example
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// gateway!(|request, _| {
+///   Ok(lando::ranged(request.headers(), "a large report"))
+/// });
+/// # fn main() { }
+/// ```
+pub fn ranged<B>(request_headers: &HeaderMap, body: B) -> Response<Body>
+where
+    B: Into<Body>,
+{
+    let body = body.into();
+    let content_type = default_content_type(&body);
+    ranged_with_content_type(request_headers, content_type, body)
+}
+
+/// Like [ranged](fn.ranged.html) but with an explicit `Content-Type` for the
+/// represented body
+pub fn ranged_with_content_type<B>(
+    request_headers: &HeaderMap,
+    content_type: &str,
+    body: B,
+) -> Response<Body>
+where
+    B: Into<Body>,
+{
+    let body = body.into();
+    let content_type = HeaderValue::from_str(content_type)
+        .unwrap_or_else(|_| HeaderValue::from_static(default_content_type(&body)));
+    let data = match body {
+        Body::Empty => Bytes::new(),
+        Body::Text(ref bytes) | Body::Binary(ref bytes) => bytes.clone(),
+    };
+    let ranges = request_headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| parse(value, data.len()))
+        .unwrap_or(Ranges::Full);
+
+    let mut response = Response::new(Body::Empty);
+    response
+        .headers_mut()
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    match ranges {
+        Ranges::Full => {
+            response.headers_mut().insert(CONTENT_TYPE, content_type);
+            *response.body_mut() = body;
+        }
+        Ranges::Unsatisfiable => {
+            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", data.len())) {
+                response.headers_mut().insert(CONTENT_RANGE, value);
+            }
+        }
+        Ranges::Partial(ref ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            response.headers_mut().insert(CONTENT_TYPE, content_type);
+            if let Ok(value) = HeaderValue::from_str(&range.content_range(data.len())) {
+                response.headers_mut().insert(CONTENT_RANGE, value);
+            }
+            *response.body_mut() = Body::Binary(data.slice(range.start, range.end + 1));
+        }
+        Ranges::Partial(ranges) => {
+            let boundary = boundary(&data);
+            let mut multipart = BytesMut::new();
+            for range in ranges {
+                let head = format!(
+                    "--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                    boundary,
+                    content_type.to_str().unwrap_or_default(),
+                    range.content_range(data.len())
+                );
+                multipart.extend_from_slice(head.as_bytes());
+                multipart.extend_from_slice(&data[range.start..=range.end]);
+                multipart.extend_from_slice(b"\r\n");
+            }
+            multipart.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            if let Ok(value) =
+                HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", boundary))
+            {
+                response.headers_mut().insert(CONTENT_TYPE, value);
+            }
+            *response.body_mut() = Body::Binary(multipart.freeze());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(range: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, range.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(
+            parse("bytes=0-4", 10),
+            Ranges::Partial(vec![ByteRange { start: 0, end: 4 }])
+        );
+        assert_eq!(
+            parse("bytes=5-", 10),
+            Ranges::Partial(vec![ByteRange { start: 5, end: 9 }])
+        );
+        assert_eq!(
+            parse("bytes=-3", 10),
+            Ranges::Partial(vec![ByteRange { start: 7, end: 9 }])
+        );
+        assert_eq!(
+            parse("bytes=0-1, 8-20", 10),
+            Ranges::Partial(vec![
+                ByteRange { start: 0, end: 1 },
+                ByteRange { start: 8, end: 9 }
+            ])
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_ranges() {
+        assert_eq!(parse("items=0-4", 10), Ranges::Full);
+        assert_eq!(parse("bytes=4-0", 10), Ranges::Full);
+        assert_eq!(parse("bytes=a-b", 10), Ranges::Full);
+        assert_eq!(parse("bytes", 10), Ranges::Full);
+    }
+
+    #[test]
+    fn detects_unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=10-", 10), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 10), Ranges::Unsatisfiable);
+    }
+
+    #[test]
+    fn responds_with_full_body_without_range() {
+        let response = ranged(&HeaderMap::new(), "hello");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), &Body::from("hello"));
+        assert_eq!(response.headers()[ACCEPT_RANGES], "bytes");
+    }
+
+    #[test]
+    fn responds_with_single_range() {
+        let response = ranged(&headers("bytes=1-3"), "hello");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 1-3/5");
+        assert_eq!(response.body(), &Body::from("ell".as_bytes()));
+    }
+
+    #[test]
+    fn responds_with_multiple_ranges() {
+        let response = ranged_with_content_type(&headers("bytes=0-0,-1"), "text/csv", "hello");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let boundary = boundary(b"hello");
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            format!("multipart/byteranges; boundary={}", boundary).as_str()
+        );
+        assert_eq!(
+            ::std::str::from_utf8(response.body()).unwrap(),
+            format!(
+                "--{b}\r\nContent-Type: text/csv\r\nContent-Range: bytes 0-0/5\r\n\r\nh\r\n\
+                 --{b}\r\nContent-Type: text/csv\r\nContent-Range: bytes 4-4/5\r\n\r\no\r\n\
+                 --{b}--\r\n",
+                b = boundary
+            )
+        );
+    }
+
+    #[test]
+    fn responds_with_unsatisfiable_range() {
+        let response = ranged(&headers("bytes=10-20"), "hello");
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes */5");
+        assert_eq!(response.body(), &Body::Empty);
+    }
+}
@@ -4,6 +4,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
+use std::io;
 use std::mem;
 
 // Third Party
@@ -11,12 +12,13 @@ use http::header::{HeaderValue, HOST};
 use http::Request as HttpRequest;
 use http::{self, HeaderMap, Method};
 use serde::{de::Error as DeError, de::MapAccess, de::Visitor, Deserialize, Deserializer};
-use serde_json::Value;
+use serde_json::{self, Value};
 
 // Ours
 use body::Body;
 use ext::{PathParameters, QueryStringParameters, StageVariables};
 use strmap::StrMap;
+use Request;
 
 /// Representation of an API Gateway proxy event data
 ///
@@ -27,6 +29,7 @@ use strmap::StrMap;
 #[serde(rename_all = "camelCase")]
 pub struct GatewayRequest<'a> {
     //pub resDeserializeHeadersource: String,
+    #[serde(borrow)]
     pub(crate) path: Cow<'a, str>,
     #[serde(deserialize_with = "deserialize_method")]
     pub(crate) http_method: Method,
@@ -34,16 +37,90 @@ pub struct GatewayRequest<'a> {
     pub(crate) headers: HeaderMap<HeaderValue>,
     #[serde(deserialize_with = "nullable_default")]
     pub(crate) query_string_parameters: StrMap,
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub(crate) multi_value_query_string_parameters: StrMap,
     #[serde(deserialize_with = "nullable_default")]
     pub(crate) path_parameters: StrMap,
     #[serde(deserialize_with = "nullable_default")]
     pub(crate) stage_variables: StrMap,
+    #[serde(borrow, default, deserialize_with = "nullable_borrowed")]
     pub(crate) body: Option<Cow<'a, str>>,
     #[serde(default)]
     pub(crate) is_base64_encoded: bool,
     pub(crate) request_context: RequestContext,
 }
 
+impl<'a> GatewayRequest<'a> {
+    /// Deserialize a proxy event from raw json bytes, borrowing
+    /// strings from `event` rather than copying them where possible
+    pub fn from_slice(event: &'a [u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(event)
+    }
+
+    /// Return a proxy event which owns its strings and so is not tied
+    /// to the lifetime of the input it was deserialized from
+    pub fn into_owned(self) -> GatewayRequest<'static> {
+        GatewayRequest {
+            path: Cow::Owned(self.path.into_owned()),
+            http_method: self.http_method,
+            headers: self.headers,
+            query_string_parameters: self.query_string_parameters,
+            multi_value_query_string_parameters: self.multi_value_query_string_parameters,
+            path_parameters: self.path_parameters,
+            stage_variables: self.stage_variables,
+            body: self.body.map(|body| Cow::Owned(body.into_owned())),
+            is_base64_encoded: self.is_base64_encoded,
+            request_context: self.request_context,
+        }
+    }
+}
+
+impl GatewayRequest<'static> {
+    /// Deserialize an owned proxy event from a json value
+    ///
+    /// `GatewayRequest` borrows from its input, so it is not `DeserializeOwned` and
+    /// can not be deserialized with `serde_json::from_value` or `serde_json::from_reader`.
+    /// This and [from_reader](#method.from_reader) take their place.
+    pub fn from_value(event: Value) -> Result<Self, serde_json::Error> {
+        GatewayRequest::deserialize(event)
+    }
+
+    /// Deserialize an owned proxy event from a reader of json
+    pub fn from_reader<R>(event: R) -> Result<Self, serde_json::Error>
+    where
+        R: io::Read,
+    {
+        let mut deserializer = serde_json::Deserializer::from_reader(event);
+        let request = GatewayRequest::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(request)
+    }
+}
+
+/// Return a `lando::Request` deserialized directly from the raw json bytes of an
+/// API gateway proxy event
+///
+/// This is useful for runtimes which provide events as bytes and for tests. Strings
+/// are borrowed from `event` where possible while deserializing, avoiding the copies
+/// made when deserializing through an intermediate `serde_json::Value`.
+///
+/// ```rust
+/// # extern crate lando;
+/// use lando::RequestExt;
+///
+/// let request = lando::request::from_slice(
+///   include_bytes!("../tests/data/proxy_request.json")
+/// ).expect("failed to deserialize request");
+/// assert_eq!(request.uri().path(), "/test/hello");
+/// ```
+pub fn from_slice(event: &[u8]) -> Result<Request, serde_json::Error> {
+    GatewayRequest::from_slice(event).map(HttpRequest::from)
+}
+
+/// A string which is borrowed from the deserializer's input where possible
+#[derive(Deserialize)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
 /// API Gateway request context
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -120,13 +197,14 @@ where
             A: MapAccess<'de>,
         {
             let mut headers = http::HeaderMap::new();
-            while let Some((key, value)) = map.next_entry::<Cow<str>, Cow<str>>()? {
+            while let Some((BorrowedStr(key), BorrowedStr(value))) =
+                map.next_entry::<BorrowedStr, BorrowedStr>()?
+            {
                 let header_name = key
                     .parse::<http::header::HeaderName>()
                     .map_err(A::Error::custom)?;
                 let header_value =
-                    http::header::HeaderValue::from_shared(value.into_owned().into())
-                        .map_err(A::Error::custom)?;
+                    http::header::HeaderValue::from_str(&value).map_err(A::Error::custom)?;
                 headers.append(header_name, header_value);
             }
             Ok(headers)
@@ -147,6 +225,14 @@ where
     Ok(opt.unwrap_or_else(T::default))
 }
 
+/// deserializes nullable strings, borrowing them where possible
+fn nullable_borrowed<'de: 'a, 'a, D>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<BorrowedStr>::deserialize(deserializer)?.map(|value| value.0))
+}
+
 impl<'a> From<GatewayRequest<'a>> for HttpRequest<Body> {
     fn from(value: GatewayRequest) -> Self {
         let GatewayRequest {
@@ -154,6 +240,7 @@ impl<'a> From<GatewayRequest<'a>> for HttpRequest<Body> {
             http_method,
             headers,
             query_string_parameters,
+            multi_value_query_string_parameters,
             path_parameters,
             stage_variables,
             body,
@@ -175,7 +262,14 @@ impl<'a> From<GatewayRequest<'a>> for HttpRequest<Body> {
             )
         });
 
-        builder.extension(QueryStringParameters(query_string_parameters));
+        // multi-valued parameters, when provided, include all single valued parameters
+        builder.extension(QueryStringParameters(
+            if multi_value_query_string_parameters.is_empty() {
+                query_string_parameters
+            } else {
+                multi_value_query_string_parameters
+            },
+        ));
         builder.extension(PathParameters(path_parameters));
         builder.extension(StageVariables(stage_variables));
         builder.extension(request_context);
@@ -204,7 +298,8 @@ impl<'a> From<GatewayRequest<'a>> for HttpRequest<Body> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json;
+    use ext::RequestExt;
+    use serde_json::{self, json};
     use std::collections::HashMap;
 
     #[test]
@@ -233,6 +328,88 @@ mod tests {
         assert!(serde_json::from_str::<GatewayRequest>(&input).is_ok())
     }
 
+    fn event(body: &str) -> String {
+        format!(
+            r#"{{"path":"/foo","httpMethod":"POST","headers":{{"Host":"www.rust-lang.org"}},"queryStringParameters":null,"pathParameters":null,"stageVariables":null,"body":{},"requestContext":{{"accountId":"","resourceId":"","stage":"","requestId":"","resourcePath":"","httpMethod":"","apiId":"","identity":{{"sourceIp":""}}}}}}"#,
+            body
+        )
+    }
+
+    #[test]
+    fn deserializes_request_events_from_slices() {
+        let input = include_bytes!("../tests/data/proxy_request.json");
+        let gwr = GatewayRequest::from_slice(input).expect("failed to deserialize request");
+        assert!(match gwr.path {
+            Cow::Borrowed(_) => true,
+            _ => false,
+        });
+        assert_eq!(
+            gwr.request_context.request_id,
+            "41b45ea3-70b5-11e6-b7bd-69b5aaebc7d9"
+        );
+    }
+
+    #[test]
+    fn borrows_bodies_from_slices() {
+        let input = event(r#""hello""#);
+        let gwr =
+            GatewayRequest::from_slice(input.as_bytes()).expect("failed to deserialize request");
+        assert!(match gwr.body {
+            Some(Cow::Borrowed("hello")) => true,
+            _ => false,
+        });
+        let input = event("null");
+        let gwr =
+            GatewayRequest::from_slice(input.as_bytes()).expect("failed to deserialize request");
+        assert_eq!(gwr.body, None);
+    }
+
+    #[test]
+    fn deserializes_escaped_strings_from_slices() {
+        let input = event(r#""{\"a\":1}""#);
+        let gwr =
+            GatewayRequest::from_slice(input.as_bytes()).expect("failed to deserialize request");
+        assert_eq!(gwr.body, Some(Cow::Owned(r#"{"a":1}"#.into())));
+        let request = HttpRequest::from(gwr);
+        assert_eq!(request.uri(), "https://www.rust-lang.org/foo");
+        assert_eq!(request.body(), &Body::from(r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn deserializes_owned_request_events() {
+        let input = include_str!("../tests/data/proxy_request.json");
+        let from_value =
+            GatewayRequest::from_value(serde_json::from_str(input).expect("failed to parse event"))
+                .expect("failed to deserialize request");
+        let from_reader =
+            GatewayRequest::from_reader(input.as_bytes()).expect("failed to deserialize request");
+        assert_eq!(from_value.path, from_reader.path);
+        assert!(GatewayRequest::from_reader(&b"{} {}"[..]).is_err());
+        // owned requests outlive their input
+        let owned = {
+            let input = event(r#""hello""#);
+            GatewayRequest::from_slice(input.as_bytes())
+                .expect("failed to deserialize request")
+                .into_owned()
+        };
+        assert_eq!(owned.body, Some(Cow::Owned("hello".into())));
+    }
+
+    #[test]
+    fn prefers_multi_valued_query_string_parameters() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/proxy_request.json"))
+                .expect("failed to parse event");
+        event["queryStringParameters"] = json!({ "name": "you" });
+        event["multiValueQueryStringParameters"] = json!({ "name": ["me", "you"] });
+        let request = HttpRequest::from(
+            GatewayRequest::deserialize(&event).expect("failed to deserialize request"),
+        );
+        let query = request.query_string_parameters();
+        assert_eq!(query.get("name"), Some("you"));
+        assert_eq!(query.get_all("name"), vec!["me", "you"]);
+    }
+
     #[test]
     fn implements_default() {
         assert_eq!(
@@ -260,5 +437,4 @@ mod tests {
             }
         )
     }
-
 }
@@ -1,13 +1,135 @@
-//! Response types
+//! Response types and shortcuts for building common responses
+//!
+//! Unlike `Response::builder()`, these never panic. Values which can not be represented
+//! as header values, like locations containing control characters, produce
+//! `500 Internal Server Error` responses.
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! use lando::response;
+//! use lando::RequestExt;
+//!
+//! gateway!(|request, _| {
+//!   Ok(match request.path_parameters().get("id") {
+//!     Some(id) => response::see_other(&format!("/users/{}", id)),
+//!     _ => response::not_found()
+//!   })
+//! });
+//! # fn main() { }
+//! ```
 
 // Std
 use std::ops::Not;
 
-use http::header::{HeaderMap, HeaderValue};
-use http::Response as HttpResponse;
-use serde::{ser::Error as SerError, ser::SerializeMap, Serializer};
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE, LOCATION};
+use http::{Response as HttpResponse, StatusCode};
+use serde::{ser::Error as SerError, ser::SerializeMap, Serialize, Serializer};
 
 use body::Body;
+use format::JsonWithStatus;
+use IntoResponse;
+
+/// Kinds of redirection responses
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Redirect {
+    /// `308 Permanent Redirect`, the resource has permanently moved. The request method is preserved
+    Permanent,
+    /// `307 Temporary Redirect`, the resource has temporarily moved. The request method is preserved
+    Temporary,
+    /// `303 See Other`, the client should `GET` the resource at another location, typically after a `POST`
+    SeeOther,
+}
+
+impl Redirect {
+    fn status(self) -> StatusCode {
+        match self {
+            Redirect::Permanent => StatusCode::PERMANENT_REDIRECT,
+            Redirect::Temporary => StatusCode::TEMPORARY_REDIRECT,
+            Redirect::SeeOther => StatusCode::SEE_OTHER,
+        }
+    }
+}
+
+fn status(status: StatusCode) -> HttpResponse<Body> {
+    let mut response = HttpResponse::new(Body::Empty);
+    *response.status_mut() = status;
+    response
+}
+
+fn with_location(code: StatusCode, location: &str) -> HttpResponse<Body> {
+    match HeaderValue::from_str(location) {
+        Ok(value) => {
+            let mut response = status(code);
+            response.headers_mut().insert(LOCATION, value);
+            response
+        }
+        _ => status(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Return a `200 OK` response with the provided body
+pub fn ok<B>(body: B) -> HttpResponse<Body>
+where
+    B: Into<Body>,
+{
+    HttpResponse::new(body.into())
+}
+
+/// Return a `201 Created` response for a resource created at `location`
+pub fn created(location: &str) -> HttpResponse<Body> {
+    with_location(StatusCode::CREATED, location)
+}
+
+/// Return a `204 No Content` response
+pub fn no_content() -> HttpResponse<Body> {
+    status(StatusCode::NO_CONTENT)
+}
+
+/// Return a redirection response of a given kind to `location`
+pub fn redirect(kind: Redirect, location: &str) -> HttpResponse<Body> {
+    with_location(kind.status(), location)
+}
+
+/// Return a `308 Permanent Redirect` response to `location`
+pub fn permanent_redirect(location: &str) -> HttpResponse<Body> {
+    redirect(Redirect::Permanent, location)
+}
+
+/// Return a `307 Temporary Redirect` response to `location`
+pub fn temporary_redirect(location: &str) -> HttpResponse<Body> {
+    redirect(Redirect::Temporary, location)
+}
+
+/// Return a `303 See Other` response to `location`
+pub fn see_other(location: &str) -> HttpResponse<Body> {
+    redirect(Redirect::SeeOther, location)
+}
+
+/// Return a `400 Bad Request` response with a body serialized as `application/json`
+pub fn bad_request<T>(value: T) -> HttpResponse<Body>
+where
+    T: Serialize,
+{
+    JsonWithStatus(StatusCode::BAD_REQUEST, value).into_response()
+}
+
+/// Return a `404 Not Found` response
+pub fn not_found() -> HttpResponse<Body> {
+    status(StatusCode::NOT_FOUND)
+}
+
+/// Return a `200 OK` response with a `text/html` body
+pub fn html<B>(body: B) -> HttpResponse<Body>
+where
+    B: Into<Body>,
+{
+    let mut response = ok(body);
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    response
+}
 
 /// Representation of API Gateway response
 #[derive(Serialize, Debug)]
@@ -71,7 +193,7 @@ where
 #[cfg(test)]
 mod tests {
 
-    use super::GatewayResponse;
+    use super::*;
     use serde_json;
 
     #[test]
@@ -97,4 +219,46 @@ mod tests {
             r#"{"statusCode":200,"body":"foo"}"#
         );
     }
+
+    #[test]
+    fn created_has_location() {
+        let response = created("/things/1");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[LOCATION], "/things/1");
+    }
+
+    #[test]
+    fn redirects() {
+        assert_eq!(
+            permanent_redirect("/").status(),
+            StatusCode::PERMANENT_REDIRECT
+        );
+        assert_eq!(
+            temporary_redirect("/").status(),
+            StatusCode::TEMPORARY_REDIRECT
+        );
+        assert_eq!(see_other("/").status(), StatusCode::SEE_OTHER);
+    }
+
+    #[test]
+    fn invalid_locations_are_server_errors() {
+        let response = created("/things/\n1");
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().get(LOCATION).is_none());
+    }
+
+    #[test]
+    fn bad_request_has_json_body() {
+        let response = bad_request(vec!["oops"]);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.body(), &Body::from(r#"["oops"]"#));
+    }
+
+    #[test]
+    fn html_has_content_type() {
+        let response = html("<p>hi</p>");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+    }
 }
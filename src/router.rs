@@ -0,0 +1,319 @@
+//! Dispatch requests to handlers by method and path
+//!
+//! A single Lambda function may serve many routes. A [Router](struct.Router.html) matches
+//! each request's method and path against its [Routes](struct.Route.html), invoking the
+//! first matching route's handler. Parameters captured from the path are available
+//! to handlers as [path_parameters](../trait.RequestExt.html#tymethod.path_parameters).
+//!
+//! Paths may capture a single segment with `{name}` or all remaining segments with `{name+}`,
+//! mirroring API gateway's resource paths.
+//!
+//! Functions attributed with `#[lando(route = "...")]` are collected into a router
+//! with [routes!](../macro.routes.html)
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! use lando::{IntoResponse, LambdaContext, Request, RequestExt, Result};
+//!
+//! #[lando(route = "GET /users/{id}")]
+//! fn get_user(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   Ok(format!("user {}", request.path_parameters().get("id").unwrap_or_default()))
+//! }
+//!
+//! #[lando(route = "DELETE /users/{id}")]
+//! fn delete_user(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   Ok(())
+//! }
+//!
+//! gateway!(|request, context| routes![get_user, delete_user].handle(request, context));
+//! # fn main() { }
+//! ```
+//!
+//! Routers may also be assembled by hand
+//!
+//! ```rust
+//! # extern crate lando;
+//! use lando::router::Router;
+//!
+//! # fn main() {
+//! let router = Router::new()
+//!   .route("GET /", |_, _| Ok("home"))
+//!   .and_then(|router| router.route("GET /about", |_, _| Ok("about")))
+//!   .expect("invalid route");
+//! # }
+//! ```
+
+// Std
+use std::collections::HashMap;
+use std::fmt;
+
+// Third Party
+use http::header::{HeaderValue, ALLOW};
+use http::{Method, StatusCode};
+
+// Ours
+use ext::RequestExt;
+use response;
+use {Body, IntoResponse, LambdaContext, Request, Response, Result};
+
+/// Errors parsing route specifications
+#[derive(Debug, Fail, PartialEq)]
+#[fail(display = "invalid route {:?}: {}", route, reason)]
+pub struct RouteError {
+    route: String,
+    reason: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Greedy(String),
+}
+
+/// A handler for requests with a given method and path
+pub struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Box<Fn(Request, LambdaContext) -> Result<Response<Body>>>,
+}
+
+impl Route {
+    /// Return a new route for a specification of the form `METHOD /path`
+    pub fn new<F, R>(spec: &str, handler: F) -> ::std::result::Result<Self, RouteError>
+    where
+        F: Fn(Request, LambdaContext) -> Result<R> + 'static,
+        R: IntoResponse,
+    {
+        let invalid = |reason| RouteError {
+            route: spec.into(),
+            reason,
+        };
+        let mut parts = spec.split_whitespace();
+        let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(path), None) => (method, path),
+            _ => return Err(invalid("expected a method and path, like GET /users/{id}")),
+        };
+        let method =
+            Method::from_bytes(method.as_bytes()).map_err(|_| invalid("invalid method"))?;
+        if !path.starts_with('/') {
+            return Err(invalid("paths must start with /"));
+        }
+        let mut segments = Vec::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if let Some(Segment::Greedy(_)) = segments.last() {
+                return Err(invalid("{name+} must be the last segment"));
+            }
+            segments.push(
+                if segment.starts_with('{') && segment.ends_with('}') && segment.len() > 2 {
+                    let name = &segment[1..segment.len() - 1];
+                    if name.ends_with('+') {
+                        Segment::Greedy(name.trim_end_matches('+').into())
+                    } else {
+                        Segment::Param(name.into())
+                    }
+                } else if segment.contains(|c| c == '{' || c == '}') {
+                    return Err(invalid("parameters must span a whole segment"));
+                } else {
+                    Segment::Literal(segment.into())
+                },
+            );
+        }
+        Ok(Route {
+            method,
+            segments,
+            handler: Box::new(move |request, context| {
+                handler(request, context).map(IntoResponse::into_response)
+            }),
+        })
+    }
+
+    /// Return the path parameters captured from a path, if the path matches
+    fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut parts = path.split('/').filter(|part| !part.is_empty());
+        let mut params = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => params.push((name.clone(), parts.next()?.into())),
+                Segment::Greedy(name) => {
+                    let rest = parts.by_ref().collect::<Vec<_>>();
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    params.push((name.clone(), rest.join("/")));
+                }
+            }
+        }
+        match parts.next() {
+            Some(_) => None,
+            _ => Some(params),
+        }
+    }
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Route")
+            .field("method", &self.method)
+            .field("segments", &self.segments)
+            .finish()
+    }
+}
+
+/// A collection of routes
+///
+/// Requests matching no route's path receive a `404 Not Found` response. Requests
+/// matching a route's path but not its method receive a `405 Method Not Allowed` response
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    /// Return a new router without routes
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Add a route
+    pub fn with(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Add a route for a specification of the form `METHOD /path`
+    pub fn route<F, R>(self, spec: &str, handler: F) -> ::std::result::Result<Self, RouteError>
+    where
+        F: Fn(Request, LambdaContext) -> Result<R> + 'static,
+        R: IntoResponse,
+    {
+        Ok(self.with(Route::new(spec, handler)?))
+    }
+
+    /// Dispatch a request to the first route matching its method and path
+    pub fn handle(&self, request: Request, context: LambdaContext) -> Result<Response<Body>> {
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let params = match route.matches(request.uri().path()) {
+                Some(params) => params,
+                _ => continue,
+            };
+            if route.method != request.method() {
+                allowed.push(route.method.as_str());
+                continue;
+            }
+            let mut parameters = request
+                .path_parameters()
+                .iter()
+                .map(|(key, value)| (key.to_owned(), vec![value.to_owned()]))
+                .collect::<HashMap<_, _>>();
+            for (key, value) in params {
+                parameters.insert(key, vec![value]);
+            }
+            return (route.handler)(request.with_path_parameters(parameters), context);
+        }
+        if allowed.is_empty() {
+            return Ok(response::not_found());
+        }
+        let mut response = Response::new(Body::Empty);
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        if let Ok(value) = HeaderValue::from_str(&allowed.join(", ")) {
+            response.headers_mut().insert(ALLOW, value);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::RequestBuilder;
+
+    fn router() -> Router {
+        Router::new()
+            .route("GET /users/{id}", |request, _| {
+                Ok(format!(
+                    "user {}",
+                    request.path_parameters().get("id").unwrap_or_default()
+                ))
+            })
+            .and_then(|router| {
+                router.route("GET /files/{path+}", |request, _| {
+                    Ok(request
+                        .path_parameters()
+                        .get("path")
+                        .unwrap_or_default()
+                        .to_owned())
+                })
+            })
+            .and_then(|router| router.route("DELETE /users/{id}", |_, _| Ok(())))
+            .expect("invalid route")
+    }
+
+    fn dispatch(method: &str, uri: &str) -> Response<Body> {
+        router()
+            .handle(
+                RequestBuilder::new()
+                    .method(method)
+                    .uri(uri)
+                    .body(())
+                    .expect("failed to build request"),
+                LambdaContext::default(),
+            )
+            .expect("handler failed")
+    }
+
+    #[test]
+    fn rejects_invalid_routes() {
+        for spec in &[
+            "/users",
+            "GET users",
+            "GET /users extra",
+            "GET /users/{id",
+            "GET /files/{path+}/more",
+            "GET /users/id-{id}",
+        ] {
+            assert!(
+                Route::new(spec, |_, _| Ok(())).is_err(),
+                "accepted {}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn dispatches_to_matching_routes() {
+        assert_eq!(
+            dispatch("GET", "https://example.com/users/42").body(),
+            &Body::from("user 42")
+        );
+        assert_eq!(
+            dispatch("GET", "https://example.com/files/a/b.txt").body(),
+            &Body::from("a/b.txt")
+        );
+        assert_eq!(
+            dispatch("DELETE", "https://example.com/users/42").status(),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn rejects_unmatched_requests() {
+        assert_eq!(
+            dispatch("GET", "https://example.com/users").status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            dispatch("GET", "https://example.com/files").status(),
+            StatusCode::NOT_FOUND
+        );
+        let response = dispatch("POST", "https://example.com/users/42");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, DELETE");
+    }
+}
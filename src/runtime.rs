@@ -0,0 +1,344 @@
+//! Run handlers natively with Lambda's runtime API
+//!
+//! Besides the Python runtime bridged by [gateway!](../macro.gateway.html), lando handlers may
+//! run in Lambda's `provided` runtime as a native executable named `bootstrap`. The
+//! executable polls Lambda's [runtime API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html)
+//! for invocations with [run](fn.run.html), or [run_async](fn.run_async.html) for
+//! asynchronous handlers
+//!
+//! ```rust,no_run
+//! # extern crate lando;
+//! // src/bin/bootstrap.rs
+//! use lando::{IntoResponse, LambdaContext, Request, Result};
+//!
+//! fn handler(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   Ok("👋")
+//! }
+//!
+//! fn main() {
+//!   lando::runtime::run(handler)
+//! }
+//! ```
+//!
+//! Handlers behave as they do with `gateway!`: panics are isolated, lando's own errors are
+//! answered with [problems](../struct.Problem.html), other errors are reported to Lambda as
+//! invocation errors and requests carry the invocation's deadline and X-Ray trace context.
+
+// Std
+use std::env;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::process;
+use std::str;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Third Party
+use serde_json::{self, json};
+
+// Ours
+use executor;
+use request;
+use xray::TRACE_ENV;
+use {call, IntoResponse, LambdaContext, Request, Result};
+
+const API_VERSION: &str = "2018-06-01";
+
+/// A response from the runtime API
+struct ApiResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl ApiResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A minimal http/1.1 client for the runtime API, which is served over plain http
+/// on the function's host
+struct Client {
+    endpoint: String,
+}
+
+impl Client {
+    fn send(&self, method: &str, path: &str, body: &[u8]) -> io::Result<ApiResponse> {
+        let mut stream = TcpStream::connect(self.endpoint.as_str())?;
+        write!(
+            stream,
+            "{} /{}{} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            API_VERSION,
+            path,
+            self.endpoint,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw)?;
+        let split = raw
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| invalid_data("incomplete runtime API response"))?;
+        let head = str::from_utf8(&raw[..split])
+            .map_err(|_| invalid_data("invalid runtime API response headers"))?;
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| invalid_data("invalid runtime API response status"))?;
+        let headers = lines
+            .filter_map(|line| {
+                let mut pair = line.splitn(2, ':');
+                match (pair.next(), pair.next()) {
+                    (Some(name), Some(value)) => Some((name.trim().into(), value.trim().into())),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<(String, String)>>();
+        let mut body = raw[split + 4..].to_vec();
+        if let Some(length) = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.parse().ok())
+        {
+            body.truncate(length);
+        }
+        Ok(ApiResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    fn post(&self, path: &str, body: &[u8]) -> io::Result<()> {
+        match self.send("POST", path, body)?.status {
+            200..=299 => Ok(()),
+            status => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("runtime API rejected {} with status {}", path, status),
+            )),
+        }
+    }
+}
+
+/// Return the context of an invocation from runtime API headers and the function's environment
+fn context(response: &ApiResponse, request_id: &str) -> LambdaContext {
+    let env = |name| env::var(name).unwrap_or_default();
+    let remaining = response
+        .header("Lambda-Runtime-Deadline-Ms")
+        .and_then(|deadline| deadline.parse::<u64>().ok())
+        .and_then(|deadline| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Duration::from_millis(deadline).checked_sub(now)
+        })
+        .unwrap_or_default();
+    LambdaContext::default()
+        .with_function_name(env("AWS_LAMBDA_FUNCTION_NAME"))
+        .with_function_version(env("AWS_LAMBDA_FUNCTION_VERSION"))
+        .with_invoked_function_arn(
+            response
+                .header("Lambda-Runtime-Invoked-Function-Arn")
+                .unwrap_or_default(),
+        )
+        .with_memory_limit_in_mb(
+            env("AWS_LAMBDA_FUNCTION_MEMORY_SIZE")
+                .parse()
+                .unwrap_or_default(),
+        )
+        .with_aws_request_id(request_id)
+        .with_log_group_name(env("AWS_LAMBDA_LOG_GROUP_NAME"))
+        .with_log_stream_name(env("AWS_LAMBDA_LOG_STREAM_NAME"))
+        .with_deadline(Instant::now() + remaining)
+}
+
+/// Report an invocation which could not be handled
+fn fail(client: &Client, request_id: &str, error_type: &str, message: String) -> io::Result<()> {
+    let error = json!({ "errorMessage": message, "errorType": error_type });
+    client.post(
+        &format!("/runtime/invocation/{}/error", request_id),
+        error.to_string().as_bytes(),
+    )
+}
+
+/// Wait for the next invocation, handle it and report its outcome
+fn next<F, R>(client: &Client, handler: &F) -> io::Result<()>
+where
+    F: Fn(Request, LambdaContext) -> Result<R>,
+    R: IntoResponse,
+{
+    let invocation = client.send("GET", "/runtime/invocation/next", &[])?;
+    let request_id = invocation
+        .header("Lambda-Runtime-Aws-Request-Id")
+        .ok_or_else(|| invalid_data("invocation without a request id"))?
+        .to_owned();
+    // the X-Ray sdk and RequestExt#trace_context expect the invocation's trace here
+    match invocation.header("Lambda-Runtime-Trace-Id") {
+        Some(trace) => env::set_var(TRACE_ENV, trace),
+        _ => env::remove_var(TRACE_ENV),
+    }
+    let request = match request::from_slice(&invocation.body) {
+        Ok(request) => request,
+        Err(err) => return fail(client, &request_id, "InvalidEvent", err.to_string()),
+    };
+    let context = context(&invocation, &request_id);
+    let response = match call(handler, request, context) {
+        Ok(response) => response,
+        Err(err) => return fail(client, &request_id, "HandlerError", err.to_string()),
+    };
+    match serde_json::to_vec(&response) {
+        Ok(response) => client.post(
+            &format!("/runtime/invocation/{}/response", request_id),
+            &response,
+        ),
+        Err(err) => fail(client, &request_id, "InvalidResponse", err.to_string()),
+    }
+}
+
+/// Serve invocations with `handler` until the process is stopped
+///
+/// Exits when Lambda's runtime API is unavailable, which is the case outside of
+/// Lambda's `provided` runtime.
+pub fn run<F, R>(handler: F) -> !
+where
+    F: Fn(Request, LambdaContext) -> Result<R>,
+    R: IntoResponse,
+{
+    let client = match env::var("AWS_LAMBDA_RUNTIME_API") {
+        Ok(endpoint) => Client { endpoint },
+        _ => {
+            eprintln!("error: AWS_LAMBDA_RUNTIME_API is not set. Is this running in Lambda?");
+            process::exit(1)
+        }
+    };
+    loop {
+        if let Err(err) = next(&client, &handler) {
+            eprintln!("error: runtime API failure: {}", err);
+            process::exit(1)
+        }
+    }
+}
+
+/// Serve invocations with an asynchronous handler, driving its futures with
+/// [executor::blocking](../executor/fn.blocking.html), until the process is stopped
+pub fn run_async<F, Fut, R>(handler: F) -> !
+where
+    F: Fn(Request, LambdaContext) -> Fut,
+    Fut: Future<Output = Result<R>>,
+    R: IntoResponse,
+{
+    run(move |request, context| executor::blocking(&handler)(request, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ext::RequestExt;
+    use serde_json::Value;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    use std::thread;
+    use testing::{EventBuilder, Trigger};
+
+    /// Serve one invocation of `event` from a stand-in runtime API, returning the
+    /// path and body of the runtime's report
+    fn serve(
+        event: String,
+        handler: fn(Request, LambdaContext) -> Result<String>,
+    ) -> (String, Value) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind runtime API");
+        let client = Client {
+            endpoint: listener.local_addr().unwrap().to_string(),
+        };
+        let deadline =
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + Duration::from_secs(60);
+        let api = thread::spawn(move || {
+            let mut report = (String::new(), Value::Null);
+            // one connection polling for the invocation and one reporting its outcome
+            for response in &[event.as_str(), ""] {
+                let (connection, _) = listener.accept().expect("failed to accept");
+                let mut connection = BufReader::new(connection);
+                let mut request_line = String::new();
+                connection.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    connection.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if line.to_lowercase().starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                connection.read_exact(&mut body).unwrap();
+                if request_line.starts_with("POST") {
+                    report = (
+                        request_line.split_whitespace().nth(1).unwrap().to_owned(),
+                        serde_json::from_slice(&body).unwrap(),
+                    );
+                }
+                write!(
+                    connection.get_mut(),
+                    "HTTP/1.1 200 OK\r\nLambda-Runtime-Aws-Request-Id: req-1\r\n\
+                     Lambda-Runtime-Deadline-Ms: {}\r\n\
+                     Content-Length: {}\r\n\r\n{}",
+                    deadline.as_secs() * 1000,
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+            report
+        });
+        next(&client, &handler).expect("failed to handle invocation");
+        api.join().expect("runtime API failed")
+    }
+
+    #[test]
+    fn reports_responses() {
+        let event = EventBuilder::new(Trigger::RestApi)
+            .path("/orders")
+            .to_json();
+        let (path, response) = serve(event, |request, context| {
+            assert!(request.remaining_time().is_some());
+            Ok(format!(
+                "{} {}",
+                context.aws_request_id(),
+                request.uri().path()
+            ))
+        });
+        assert_eq!(path, "/2018-06-01/runtime/invocation/req-1/response");
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["body"], "req-1 /orders");
+    }
+
+    #[test]
+    fn reports_handler_errors() {
+        let event = EventBuilder::new(Trigger::RestApi).to_json();
+        let (path, error) = serve(event, |_, _| Err("boom".into()));
+        assert_eq!(path, "/2018-06-01/runtime/invocation/req-1/error");
+        assert_eq!(error["errorType"], "HandlerError");
+        assert_eq!(error["errorMessage"], "boom");
+    }
+
+    #[test]
+    fn reports_invalid_events() {
+        let (path, error) = serve("{}".into(), |_, _| Ok("unreachable".into()));
+        assert_eq!(path, "/2018-06-01/runtime/invocation/req-1/error");
+        assert_eq!(error["errorType"], "InvalidEvent");
+    }
+}
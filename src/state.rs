@@ -0,0 +1,210 @@
+//! Application state shared across invocations
+//!
+//! Lambda reuses a function's container, and the process within it, across invocations.
+//! Expensive resources like database clients, configuration or HTTP clients should be
+//! built once per cold start rather than per request.
+//!
+//! [State](struct.State.html) middleware lazily constructs a value on the first invocation
+//! which needs it and makes it available to every invocation thereafter through
+//! [RequestExt#state](../trait.RequestExt.html#tymethod.state). Values are identified by type,
+//! so declare a distinct type for each kind of state.
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! use lando::state::State;
+//! use lando::middleware::wrap;
+//! use lando::{IntoResponse, LambdaContext, Request, RequestExt, Result};
+//!
+//! struct Config {
+//!   greeting: String,
+//! }
+//!
+//! fn config() -> Result<Config> {
+//!   Ok(Config {
+//!     greeting: std::env::var("GREETING")?,
+//!   })
+//! }
+//!
+//! fn handler(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   let config = request.state::<Config>().expect("missing config");
+//!   Ok(config.greeting.clone())
+//! }
+//!
+//! gateway!(wrap(State::new(config), handler));
+//! # fn main() { }
+//! ```
+//!
+//! Functions attributed with `#[lando]` may declare state with `#[lando(state = config)]`.
+//!
+//! When initialization fails, the invocation fails with a
+//! [StateError](struct.StateError.html) and initialization is attempted again on the next
+//! invocation. Initialization failures are reported as init errors rather than handler
+//! errors: they are logged to stderr prefixed with `init error` and answered with a
+//! `503 Service Unavailable` [problem](../struct.Problem.html), as the function may recover
+//! on a later invocation.
+
+// Std
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+// Ours
+use middleware::Middleware;
+use {Body, Request, Response, Result};
+
+/// An error initializing state
+#[derive(Debug)]
+pub struct StateError {
+    message: String,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to initialize state: {}", self.message)
+    }
+}
+
+impl StdError for StateError {}
+
+/// Request extension holding an initialized state value
+pub(crate) struct StateData<T>(pub(crate) Arc<T>);
+
+type Registry = Mutex<HashMap<TypeId, Arc<Any + Send + Sync>>>;
+
+lazy_static! {
+    static ref REGISTRY: Registry = Mutex::new(HashMap::new());
+}
+
+/// Return the state value of type `T`, initializing it with `init` if this process
+/// has not yet done so
+fn get_or_init<T, F>(init: F) -> ::std::result::Result<Arc<T>, StateError>
+where
+    T: Send + Sync + 'static,
+    F: FnOnce() -> Result<T>,
+{
+    let existing = REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&TypeId::of::<T>())
+        .cloned();
+    if let Some(existing) = existing {
+        return Ok(downcast(existing));
+    }
+    // the registry is not locked while initializing so that initializers
+    // may themselves depend on other state
+    let value: Arc<Any + Send + Sync> = Arc::new(init().map_err(|err| StateError {
+        message: err.to_string(),
+    })?);
+    let stored = REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(TypeId::of::<T>())
+        .or_insert(value)
+        .clone();
+    Ok(downcast(stored))
+}
+
+fn downcast<T>(value: Arc<Any + Send + Sync>) -> Arc<T>
+where
+    T: Send + Sync + 'static,
+{
+    value
+        .downcast()
+        .unwrap_or_else(|_| unreachable!("state is registered by type"))
+}
+
+/// Middleware providing a lazily initialized value of type `T` to handlers
+pub struct State<F, T> {
+    init: F,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<F, T> State<F, T>
+where
+    F: Fn() -> Result<T>,
+    T: Send + Sync + 'static,
+{
+    /// Return new state middleware which initializes its value with `init`
+    pub fn new(init: F) -> Self {
+        State {
+            init,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<F, T> Middleware for State<F, T>
+where
+    F: Fn() -> Result<T>,
+    T: Send + Sync + 'static,
+{
+    fn handle<N>(&self, mut request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        let value = get_or_init(&self.init)?;
+        request.extensions_mut().insert(StateData(value));
+        next(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ext::RequestExt;
+    use http::StatusCode;
+    use middleware::wrap;
+    use problem;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn initializes_state_once() {
+        struct Counter(usize);
+        static INITIALIZED: AtomicUsize = AtomicUsize::new(0);
+        let init = || Ok(Counter(INITIALIZED.fetch_add(1, Ordering::SeqCst) + 1));
+        for _ in 0..3 {
+            let response = wrap(State::new(init), |request: Request, _: ()| {
+                Ok(request
+                    .state::<Counter>()
+                    .map(|counter| counter.0.to_string())
+                    .unwrap_or_default())
+            })(Request::new(Body::Empty), ())
+            .expect("handler failed");
+            assert_eq!(response.body(), &Body::from("1"));
+        }
+        assert_eq!(INITIALIZED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retries_failed_initialization() {
+        struct Flaky;
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        let init = || match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+            0 => Err("connection refused".into()),
+            _ => Ok(Flaky),
+        };
+        let handler = |request: Request, _: ()| Ok(request.state::<Flaky>().is_some().to_string());
+        let err = wrap(State::new(init), handler)(Request::new(Body::Empty), ())
+            .expect_err("initialization succeeded");
+        assert_eq!(
+            err.to_string(),
+            "failed to initialize state: connection refused"
+        );
+        assert_eq!(
+            problem::from_error(err.as_ref()).map(|problem| problem.status()),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        let response =
+            wrap(State::new(init), handler)(Request::new(Body::Empty), ()).expect("handler failed");
+        assert_eq!(response.body(), &Body::from("true"));
+    }
+
+    #[test]
+    fn requests_without_state() {
+        struct Missing;
+        assert!(Request::new(Body::Empty).state::<Missing>().is_none());
+    }
+}
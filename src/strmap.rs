@@ -1,49 +1,198 @@
-use std::collections::{hash_map::Keys, HashMap};
+use std::collections::{hash_map, HashMap};
+use std::error::Error as StdError;
 use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde::{de::MapAccess, de::Visitor, Deserialize, Deserializer};
 
 /// A read-only view into a map of string data
+///
+/// Keys may be associated with multiple values, as is the case for
+/// query string parameters provided more than once. Lookups are case sensitive by
+/// default and may be made case insensitive with
+/// [case_insensitive](struct.StrMap.html#method.case_insensitive)
 #[derive(Default, Debug, PartialEq)]
-pub struct StrMap(pub(crate) Arc<HashMap<String, String>>);
+pub struct StrMap {
+    data: Arc<HashMap<String, Vec<String>>>,
+    case_insensitive: bool,
+}
 
 impl StrMap {
-    /// Return a named value where available
+    /// Return a named value where available. If multiple values are
+    /// associated with a key, the last value is returned
     pub fn get(&self, key: &str) -> Option<&str> {
-        self.0.get(key).map(|value| value.as_ref())
+        self.values(key)
+            .and_then(|values| values.last())
+            .map(|value| value.as_ref())
+    }
+
+    /// Return all values associated with a key, in the order they were provided.
+    /// An absent key yields an empty list
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.values(key)
+            .map(|values| values.iter().map(|value| value.as_ref()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Return true if a key is associated with at least one value
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.values(key).is_some()
+    }
+
+    /// Return a named value parsed into type `T`
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::{ParseError, StrMap};
+    ///
+    /// let query = vec![("page", "2"), ("size", "large")]
+    ///   .into_iter()
+    ///   .collect::<StrMap>();
+    /// assert_eq!(query.parse::<usize>("page"), Ok(2));
+    /// assert!(match query.parse::<usize>("size") {
+    ///   Err(ParseError::Invalid { .. }) => true,
+    ///   _ => false
+    /// });
+    /// assert!(match query.parse::<usize>("offset") {
+    ///   Err(ParseError::Missing { .. }) => true,
+    ///   _ => false
+    /// });
+    /// ```
+    pub fn parse<T>(&self, key: &str) -> Result<T, ParseError<T::Err>>
+    where
+        T: FromStr,
+    {
+        match self.get(key) {
+            Some(value) => value.parse().map_err(|error| ParseError::Invalid {
+                key: key.into(),
+                value: value.into(),
+                error,
+            }),
+            _ => Err(ParseError::Missing { key: key.into() }),
+        }
     }
 
     /// Return true if the underlying map is empty
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.data.is_empty()
     }
 
-    /// Return an iterator over keys and values
+    /// Return the number of keys in the map
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Return an iterator over keys
+    pub fn keys(&self) -> StrMapKeys {
+        StrMapKeys {
+            keys: self.data.keys(),
+        }
+    }
+
+    /// Return an iterator over keys and values. Keys associated with multiple
+    /// values are yielded once for each value
     pub fn iter(&self) -> StrMapIter {
         StrMapIter {
-            data: self,
-            keys: self.0.keys(),
+            entries: self.data.iter(),
+            current: None,
         }
     }
+
+    /// Return a view of this map whose lookups ignore the ascii case of keys
+    ///
+    /// ```rust
+    /// # extern crate lando;
+    /// use lando::StrMap;
+    ///
+    /// let query = vec![("Page", "2")].into_iter().collect::<StrMap>();
+    /// assert_eq!(query.get("page"), None);
+    /// assert_eq!(query.case_insensitive().get("page"), Some("2"));
+    /// ```
+    pub fn case_insensitive(&self) -> Self {
+        StrMap {
+            data: self.data.clone(),
+            case_insensitive: true,
+        }
+    }
+
+    fn values(&self, key: &str) -> Option<&Vec<String>> {
+        self.data.get(key).or_else(|| {
+            if self.case_insensitive {
+                self.data
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(|(_, values)| values)
+            } else {
+                None
+            }
+        })
+    }
 }
 
 impl Clone for StrMap {
     fn clone(&self) -> Self {
         // only clone the inner data
-        StrMap(self.0.clone())
+        StrMap {
+            data: self.data.clone(),
+            case_insensitive: self.case_insensitive,
+        }
     }
 }
+
 impl From<HashMap<String, String>> for StrMap {
     fn from(inner: HashMap<String, String>) -> Self {
-        StrMap(Arc::new(inner))
+        inner.into_iter().collect()
+    }
+}
+
+impl From<HashMap<String, Vec<String>>> for StrMap {
+    fn from(inner: HashMap<String, Vec<String>>) -> Self {
+        StrMap {
+            data: Arc::new(
+                inner
+                    .into_iter()
+                    .filter(|(_, values)| !values.is_empty())
+                    .collect(),
+            ),
+            case_insensitive: false,
+        }
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for StrMap
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut data: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, value) in iter {
+            data.entry(key.into()).or_default().push(value.into());
+        }
+        StrMap::from(data)
+    }
+}
+
+impl<'a> IntoIterator for &'a StrMap {
+    type Item = (&'a str, &'a str);
+    type IntoIter = StrMapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
 /// A read only reference to `StrMap` key and value slice pairings
 pub struct StrMapIter<'a> {
-    data: &'a StrMap,
-    keys: Keys<'a, String, String>,
+    entries: hash_map::Iter<'a, String, Vec<String>>,
+    current: Option<(&'a str, slice::Iter<'a, String>)>,
 }
 
 impl<'a> Iterator for StrMapIter<'a> {
@@ -51,12 +200,92 @@ impl<'a> Iterator for StrMapIter<'a> {
 
     #[inline]
     fn next(&mut self) -> Option<(&'a str, &'a str)> {
-        self.keys
-            .next()
-            .and_then(|k| self.data.get(k).map(|v| (k.as_str(), v)))
+        loop {
+            if let Some((key, ref mut values)) = self.current {
+                if let Some(value) = values.next() {
+                    return Some((key, value.as_str()));
+                }
+            }
+            let (key, values) = self.entries.next()?;
+            self.current = Some((key.as_str(), values.iter()));
+        }
+    }
+}
+
+/// A read only reference to `StrMap` keys
+pub struct StrMapKeys<'a> {
+    keys: hash_map::Keys<'a, String, Vec<String>>,
+}
+
+impl<'a> Iterator for StrMapKeys<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        self.keys.next().map(|key| key.as_str())
+    }
+}
+
+/// Errors parsing `StrMap` values with
+/// [StrMap#parse](struct.StrMap.html#method.parse)
+#[derive(Debug, PartialEq)]
+pub enum ParseError<E> {
+    /// Returned when no value is associated with a key
+    Missing {
+        /// The requested key
+        key: String,
+    },
+    /// Returned when a value fails to parse
+    Invalid {
+        /// The requested key
+        key: String,
+        /// The value which failed to parse
+        value: String,
+        /// The underlying parse error
+        error: E,
+    },
+}
+
+impl<E> fmt::Display for ParseError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Missing { key } => write!(f, "missing value for {}", key),
+            ParseError::Invalid { key, value, error } => {
+                write!(f, "invalid value {:?} for {}: {}", value, key, error)
+            }
+        }
     }
 }
 
+impl<E> StdError for ParseError<E> where E: StdError {}
+
+impl Serialize for StrMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.data.len()))?;
+        for (key, values) in self.data.iter() {
+            match values.as_slice() {
+                [value] => map.serialize_entry(key, value)?,
+                values => map.serialize_entry(key, values)?,
+            }
+        }
+        map.end()
+    }
+}
+
+/// A single value or sequence of values
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+
 impl<'de> Deserialize<'de> for StrMap {
     fn deserialize<D>(deserializer: D) -> Result<StrMap, D::Error>
     where
@@ -76,10 +305,16 @@ impl<'de> Deserialize<'de> for StrMap {
                 A: MapAccess<'de>,
             {
                 let mut inner = HashMap::new();
-                while let Some((key, value)) = map.next_entry()? {
-                    inner.insert(key, value);
+                while let Some((key, values)) = map.next_entry()? {
+                    inner.insert(
+                        key,
+                        match values {
+                            Values::One(value) => vec![value],
+                            Values::Many(values) => values,
+                        },
+                    );
                 }
-                Ok(StrMap(Arc::new(inner)))
+                Ok(StrMap::from(inner))
             }
         }
 
@@ -90,6 +325,7 @@ impl<'de> Deserialize<'de> for StrMap {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::{self, json};
     use std::collections::HashMap;
 
     #[test]
@@ -99,21 +335,88 @@ mod tests {
 
     #[test]
     fn str_map_get() {
-        let mut data = HashMap::new();
+        let mut data: HashMap<String, String> = HashMap::new();
         data.insert("foo".into(), "bar".into());
-        let strmap = StrMap(data.into());
+        let strmap = StrMap::from(data);
         assert_eq!(strmap.get("foo"), Some("bar"));
         assert_eq!(strmap.get("bar"), None);
     }
 
     #[test]
     fn str_map_iter() {
-        let mut data = HashMap::new();
+        let mut data: HashMap<String, String> = HashMap::new();
         data.insert("foo".into(), "bar".into());
         data.insert("baz".into(), "boom".into());
-        let strmap = StrMap(data.into());
+        let strmap = StrMap::from(data);
         let mut values = strmap.iter().map(|(_, v)| v).collect::<Vec<_>>();
         values.sort();
         assert_eq!(values, vec!["bar", "boom"]);
     }
+
+    #[test]
+    fn str_map_multiple_values() {
+        let strmap = vec![("foo", "bar"), ("foo", "baz"), ("boom", "bap")]
+            .into_iter()
+            .collect::<StrMap>();
+        assert_eq!(strmap.len(), 2);
+        assert!(strmap.contains_key("foo"));
+        assert_eq!(strmap.get("foo"), Some("baz"));
+        assert_eq!(strmap.get_all("foo"), vec!["bar", "baz"]);
+        assert!(strmap.get_all("nope").is_empty());
+        let mut keys = strmap.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["boom", "foo"]);
+        let mut pairs = (&strmap).into_iter().collect::<Vec<_>>();
+        pairs.sort();
+        assert_eq!(pairs, vec![("boom", "bap"), ("foo", "bar"), ("foo", "baz")]);
+    }
+
+    #[test]
+    fn str_map_case_insensitive() {
+        let strmap = vec![("Content-Type", "text/plain")]
+            .into_iter()
+            .collect::<StrMap>();
+        assert!(!strmap.contains_key("content-type"));
+        let strmap = strmap.case_insensitive();
+        assert!(strmap.contains_key("content-type"));
+        assert_eq!(strmap.clone().get("CONTENT-TYPE"), Some("text/plain"));
+    }
+
+    #[test]
+    fn str_map_parse() {
+        let strmap = vec![("page", "2"), ("size", "large")]
+            .into_iter()
+            .collect::<StrMap>();
+        assert_eq!(strmap.parse::<u8>("page"), Ok(2));
+        match strmap.parse::<u8>("size") {
+            Err(ref err @ ParseError::Invalid { .. }) => assert_eq!(
+                err.to_string(),
+                "invalid value \"large\" for size: invalid digit found in string"
+            ),
+            other => assert!(false, "unexpected result {:?}", other),
+        }
+        assert_eq!(
+            strmap.parse::<u8>("offset"),
+            Err(ParseError::Missing {
+                key: "offset".into()
+            })
+        );
+    }
+
+    #[test]
+    fn str_map_serde_round_trip() {
+        let strmap = serde_json::from_value::<StrMap>(json!({
+            "foo": "bar",
+            "baz": ["a", "b"]
+        }))
+        .expect("failed to deserialize");
+        assert_eq!(strmap.get_all("baz"), vec!["a", "b"]);
+        assert_eq!(
+            serde_json::to_value(&strmap).expect("failed to serialize"),
+            json!({
+                "foo": "bar",
+                "baz": ["a", "b"]
+            })
+        );
+    }
 }
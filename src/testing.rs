@@ -0,0 +1,511 @@
+//! Utilities for testing handlers
+//!
+//! These are only needed by tests, so they are kept out of deployed functions unless the
+//! `testing` cargo feature is enabled. Enable it for your tests with a dev-dependency
+//!
+//! ```toml
+//! [dev-dependencies]
+//! lando = { version = "0.2", features = ["testing"] }
+//! ```
+//!
+//! Handlers are typically exercised with requests deserialized from API gateway events. The
+//! [RequestBuilder](struct.RequestBuilder.html) builds equivalent requests, including API gateway
+//! specific data like path parameters and request context, without the need for json fixtures.
+//!
+//! ```rust
+//! # extern crate lando;
+//! use lando::testing::RequestBuilder;
+//! use lando::{Request, RequestExt};
+//!
+//! fn greet(request: Request) -> String {
+//!   format!(
+//!     "hello {}",
+//!     request.path_parameters().get("name").unwrap_or("stranger")
+//!   )
+//! }
+//!
+//! # fn main() {
+//! let request = RequestBuilder::new()
+//!   .uri("https://example.com/hello/lando")
+//!   .path_parameter("name", "lando")
+//!   .body(())
+//!   .expect("failed to build request");
+//! assert_eq!(greet(request), "hello lando");
+//! # }
+//! ```
+//!
+//! Canonical sample events for each Lambda [Trigger](enum.Trigger.html) are also provided.
+//! An [EventBuilder](struct.EventBuilder.html) tailors them to a test's needs, which makes
+//! table driven tests straightforward.
+//!
+//! ```rust
+//! # extern crate lando;
+//! use lando::testing::{EventBuilder, Trigger};
+//!
+//! # fn main() {
+//! for (method, path) in vec![("GET", "/orders"), ("POST", "/orders")] {
+//!   let request = EventBuilder::new(Trigger::RestApi)
+//!     .method(method)
+//!     .path(path)
+//!     .header("Content-Type", "application/json")
+//!     .request()
+//!     .expect("failed to deserialize request");
+//!   assert_eq!(request.method(), method);
+//!   assert_eq!(request.uri().path(), path);
+//! }
+//! # }
+//! ```
+
+// Std
+use std::collections::HashMap;
+
+// Third Party
+use base64;
+use http::header::{HeaderName, HeaderValue};
+use http::request::Builder;
+use http::{self, HttpTryFrom, Method, Uri};
+use serde_json::{self, Value};
+
+// Ours
+use ext::RequestExt;
+use request::{self, RequestContext};
+use strmap::StrMap;
+use {Body, Request};
+
+/// A builder for `lando::Request`s with API gateway specific data
+///
+/// Requests default to a `GET` method and a `/` uri
+#[derive(Debug, Default)]
+pub struct RequestBuilder {
+    inner: Builder,
+    query_string_parameters: HashMap<String, Vec<String>>,
+    path_parameters: HashMap<String, String>,
+    stage_variables: HashMap<String, String>,
+    request_context: RequestContext,
+}
+
+impl RequestBuilder {
+    /// Return a new request builder
+    pub fn new() -> Self {
+        RequestBuilder::default()
+    }
+
+    /// Set the http method
+    pub fn method<M>(mut self, method: M) -> Self
+    where
+        Method: HttpTryFrom<M>,
+    {
+        self.inner.method(method);
+        self
+    }
+
+    /// Set the request uri
+    pub fn uri<U>(mut self, uri: U) -> Self
+    where
+        Uri: HttpTryFrom<U>,
+    {
+        self.inner.uri(uri);
+        self
+    }
+
+    /// Append a header
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        self.inner.header(key, value);
+        self
+    }
+
+    /// Append a query string parameter. Parameters may be provided more than once
+    pub fn query_string_parameter<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.query_string_parameters
+            .entry(key.into())
+            .or_default()
+            .push(value.into());
+        self
+    }
+
+    /// Set a path parameter
+    pub fn path_parameter<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.path_parameters.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set a stage variable
+    pub fn stage_variable<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.stage_variables.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the API gateway request context
+    pub fn request_context(mut self, context: RequestContext) -> Self {
+        self.request_context = context;
+        self
+    }
+
+    /// Return a request with a given body, failing if an invalid
+    /// method, uri or header was provided
+    pub fn body<B>(self, body: B) -> Result<Request, http::Error>
+    where
+        B: Into<Body>,
+    {
+        let RequestBuilder {
+            mut inner,
+            query_string_parameters,
+            path_parameters,
+            stage_variables,
+            request_context,
+        } = self;
+        Ok(inner
+            .body(body.into())?
+            .with_query_string_parameters(StrMap::from(query_string_parameters))
+            .with_path_parameters(StrMap::from(path_parameters))
+            .with_stage_variables(StrMap::from(stage_variables))
+            .with_request_context(request_context))
+    }
+}
+
+/// Lambda triggers for which sample events are provided
+///
+/// Lando handlers are invoked with `RestApi` events. Samples of other triggers are provided for
+/// testing functions which handle them by other means.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// API Gateway REST API (payload format 1.0) proxy events
+    RestApi,
+    /// API Gateway HTTP API (payload format 2.0) events
+    HttpApi,
+    /// Application Load Balancer target group events
+    Alb,
+    /// API Gateway WebSocket API message events
+    WebSocket,
+    /// API Gateway `REQUEST` Lambda authorizer events
+    Authorizer,
+    /// Lambda Function URL events
+    FunctionUrl,
+}
+
+impl Trigger {
+    /// Return all triggers
+    pub fn all() -> &'static [Trigger] {
+        &[
+            Trigger::RestApi,
+            Trigger::HttpApi,
+            Trigger::Alb,
+            Trigger::WebSocket,
+            Trigger::Authorizer,
+            Trigger::FunctionUrl,
+        ]
+    }
+
+    /// Return the json of a canonical sample event
+    pub fn sample(&self) -> &'static str {
+        match self {
+            Trigger::RestApi => include_str!("events/rest_api.json"),
+            Trigger::HttpApi => include_str!("events/http_api.json"),
+            Trigger::Alb => include_str!("events/alb.json"),
+            Trigger::WebSocket => include_str!("events/websocket.json"),
+            Trigger::Authorizer => include_str!("events/authorizer.json"),
+            Trigger::FunctionUrl => include_str!("events/function_url.json"),
+        }
+    }
+
+    /// Return true for triggers using API Gateway's 2.0 payload format
+    fn is_v2(&self) -> bool {
+        match self {
+            Trigger::HttpApi | Trigger::FunctionUrl => true,
+            _ => false,
+        }
+    }
+}
+
+/// A builder for sample events, emitted as json
+///
+/// Each setter updates the fields of the event the trigger defines for it. Setters
+/// for fields a trigger does not define, like the method of WebSocket messages or
+/// the body of authorizer events, have no effect.
+#[derive(Debug, Clone)]
+pub struct EventBuilder {
+    trigger: Trigger,
+    event: Value,
+}
+
+impl EventBuilder {
+    /// Return a new builder starting from a trigger's sample event
+    pub fn new(trigger: Trigger) -> Self {
+        EventBuilder {
+            trigger,
+            event: serde_json::from_str(trigger.sample()).expect("invalid sample event"),
+        }
+    }
+
+    /// Set the http method
+    pub fn method<M>(mut self, method: M) -> Self
+    where
+        M: Into<String>,
+    {
+        let method = Value::from(method.into());
+        match self.trigger {
+            Trigger::RestApi | Trigger::Authorizer => {
+                set(&mut self.event, &["httpMethod"], method.clone());
+                set(&mut self.event, &["requestContext", "httpMethod"], method);
+            }
+            Trigger::Alb => set(&mut self.event, &["httpMethod"], method),
+            Trigger::HttpApi | Trigger::FunctionUrl => set(
+                &mut self.event,
+                &["requestContext", "http", "method"],
+                method,
+            ),
+            Trigger::WebSocket => (),
+        }
+        self
+    }
+
+    /// Set the request path
+    pub fn path<P>(mut self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        let path = Value::from(path.into());
+        match self.trigger {
+            Trigger::RestApi | Trigger::Alb => set(&mut self.event, &["path"], path),
+            Trigger::Authorizer => {
+                set(&mut self.event, &["path"], path.clone());
+                set(&mut self.event, &["requestContext", "path"], path);
+            }
+            Trigger::HttpApi | Trigger::FunctionUrl => {
+                set(&mut self.event, &["rawPath"], path.clone());
+                set(&mut self.event, &["requestContext", "http", "path"], path);
+            }
+            Trigger::WebSocket => (),
+        }
+        self
+    }
+
+    /// Append a header. 2.0 payload format events combine repeated headers with commas
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (name, value) = (name.into(), value.into());
+        if self.trigger.is_v2() {
+            let name = name.to_lowercase();
+            let combined = match self.event["headers"].get(&name).and_then(Value::as_str) {
+                Some(existing) => format!("{},{}", existing, value),
+                _ => value,
+            };
+            set(&mut self.event, &["headers", &name], combined.into());
+            return self;
+        }
+        let name = match self.trigger {
+            Trigger::Alb => name.to_lowercase(),
+            _ => name,
+        };
+        set(&mut self.event, &["headers", &name], value.clone().into());
+        if self
+            .event
+            .get("multiValueHeaders")
+            .map_or(false, Value::is_object)
+        {
+            let values = self.event["multiValueHeaders"]
+                .as_object_mut()
+                .expect("multiValueHeaders is an object")
+                .entry(name)
+                .or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(values) = values {
+                values.push(value.into());
+            }
+        }
+        self
+    }
+
+    /// Set the request body
+    pub fn body<B>(mut self, body: B) -> Self
+    where
+        B: Into<String>,
+    {
+        if self.trigger != Trigger::Authorizer {
+            set(&mut self.event, &["body"], Value::from(body.into()));
+        }
+        self
+    }
+
+    /// Set a binary request body, base64 encoding it and flagging it as such
+    pub fn binary_body<B>(self, body: B) -> Self
+    where
+        B: AsRef<[u8]>,
+    {
+        self.body(base64::encode(body.as_ref()))
+            .is_base64_encoded(true)
+    }
+
+    /// Set whether the request body is base64 encoded
+    pub fn is_base64_encoded(mut self, encoded: bool) -> Self {
+        if self.trigger != Trigger::Authorizer {
+            set(&mut self.event, &["isBase64Encoded"], Value::from(encoded));
+        }
+        self
+    }
+
+    /// Return the event as a json value
+    pub fn to_value(&self) -> Value {
+        self.event.clone()
+    }
+
+    /// Return the event as json text
+    pub fn to_json(&self) -> String {
+        self.event.to_string()
+    }
+
+    /// Return the event deserialized as a `lando::Request`, as it would be provided
+    /// to a handler. This is supported for `RestApi` events
+    pub fn request(&self) -> Result<Request, serde_json::Error> {
+        request::from_slice(self.to_json().as_bytes())
+    }
+}
+
+/// Set a value at a path of object keys, creating objects as needed
+fn set(event: &mut Value, path: &[&str], value: Value) {
+    match path.split_first() {
+        Some((key, [])) => {
+            if !event.is_object() {
+                *event = Value::Object(Default::default());
+            }
+            event[*key] = value;
+        }
+        Some((key, rest)) => {
+            if !event.is_object() {
+                *event = Value::Object(Default::default());
+            }
+            set(&mut event[*key], rest, value)
+        }
+        _ => *event = value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::CONTENT_TYPE;
+    use serde_json::json;
+
+    #[test]
+    fn builds_requests() {
+        let request = RequestBuilder::new()
+            .method("POST")
+            .uri("https://example.com/orders/42?page=2")
+            .header(CONTENT_TYPE, "application/json")
+            .query_string_parameter("page", "2")
+            .query_string_parameter("page", "3")
+            .path_parameter("id", "42")
+            .stage_variable("env", "test")
+            .request_context(RequestContext {
+                request_id: "abc-123".into(),
+                ..RequestContext::default()
+            })
+            .body("{}")
+            .expect("failed to build request");
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.uri().path(), "/orders/42");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            request.query_string_parameters().get_all("page"),
+            vec!["2", "3"]
+        );
+        assert_eq!(request.path_parameters().get("id"), Some("42"));
+        assert_eq!(request.stage_variables().get("env"), Some("test"));
+        assert_eq!(request.request_context().request_id, "abc-123");
+        assert_eq!(request.body(), &Body::from("{}"));
+    }
+
+    #[test]
+    fn sample_events_are_valid_json() {
+        for trigger in Trigger::all() {
+            assert!(
+                serde_json::from_str::<Value>(trigger.sample()).is_ok(),
+                "invalid {:?} sample",
+                trigger
+            );
+        }
+    }
+
+    #[test]
+    fn builds_events_for_each_trigger() {
+        for trigger in Trigger::all() {
+            let event = EventBuilder::new(*trigger)
+                .method("POST")
+                .path("/orders")
+                .header("X-Test", "a")
+                .header("X-Test", "b")
+                .binary_body("hello")
+                .to_value();
+            let (method, path, header) = match trigger {
+                Trigger::RestApi | Trigger::Authorizer => {
+                    ("/httpMethod", "/path", "/headers/X-Test")
+                }
+                Trigger::Alb => ("/httpMethod", "/path", "/headers/x-test"),
+                Trigger::HttpApi | Trigger::FunctionUrl => {
+                    ("/requestContext/http/method", "/rawPath", "/headers/x-test")
+                }
+                Trigger::WebSocket => ("/requestContext/eventType", "", "/headers/X-Test"),
+            };
+            match trigger {
+                Trigger::WebSocket => assert_eq!(event.pointer(method), Some(&json!("MESSAGE"))),
+                _ => {
+                    assert_eq!(event.pointer(method), Some(&json!("POST")));
+                    assert_eq!(event.pointer(path), Some(&json!("/orders")));
+                }
+            }
+            let expected_header = if trigger.is_v2() { "a,b" } else { "b" };
+            assert_eq!(event.pointer(header), Some(&json!(expected_header)));
+            if *trigger == Trigger::Authorizer {
+                assert_eq!(
+                    event.pointer("/multiValueHeaders/X-Test"),
+                    Some(&json!(["a", "b"]))
+                );
+                assert_eq!(event.get("body"), None);
+            } else {
+                assert_eq!(event.pointer("/body"), Some(&json!("aGVsbG8=")));
+                assert_eq!(event.pointer("/isBase64Encoded"), Some(&json!(true)));
+            }
+        }
+    }
+
+    #[test]
+    fn builds_rest_api_requests() {
+        let request = EventBuilder::new(Trigger::RestApi)
+            .method("PUT")
+            .path("/orders/42")
+            .header("Content-Type", "application/json")
+            .binary_body("{}")
+            .request()
+            .expect("failed to deserialize request");
+        assert_eq!(request.method(), Method::PUT);
+        assert_eq!(request.uri().path(), "/orders/42");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(request.body(), &Body::from(&b"{}"[..]));
+    }
+
+    #[test]
+    fn reports_invalid_parts() {
+        assert!(RequestBuilder::new()
+            .header("bad header", "value")
+            .body(())
+            .is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Answering requests before Lambda's hard timeout
+
+// Std
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Third Party
+use http::StatusCode;
+
+// Ours
+use ext::RequestExt;
+use middleware::Middleware;
+use problem::Problem;
+use {Body, IntoResponse, Request, Response, Result};
+
+/// Returned by asynchronous handlers whose deadline passes before they complete
+#[derive(Debug, Default, PartialEq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "deadline exceeded")
+    }
+}
+
+impl StdError for DeadlineExceeded {}
+
+/// Middleware answering requests a safety margin before the invocation times out
+///
+/// Lambda terminates invocations which run past their timeout, leaving API gateway to respond
+/// with an opaque error. `Timeout` brings each request's
+/// [deadline](trait.RequestExt.html#tymethod.deadline) forward by `margin`, leaving time to respond.
+/// Requests reaching `Timeout` after that deadline, and asynchronous handlers run with
+/// [executor::blocking](executor/fn.blocking.html) which are cancelled when it passes, are
+/// answered with a `504 Gateway Timeout` [problem](struct.Problem.html), or another
+/// configured status. Other errors are returned as they are.
+///
+/// Only asynchronous handlers are answered this way once started. Synchronous handlers can
+/// not be interrupted: their responses and errors pass through however late they are, and
+/// one still running at Lambda's timeout is terminated as it would be without `Timeout`.
+/// Bound their work, i.e. calls to downstream services, by the request's deadline.
+///
+/// ```rust
+/// # #[macro_use] extern crate lando;
+/// use lando::http::StatusCode;
+/// use lando::middleware::wrap;
+/// use lando::{IntoResponse, LambdaContext, Request, RequestExt, Result, Timeout};
+/// use std::time::Duration;
+///
+/// fn handler(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+///   // bound calls to downstream services by the time left
+///   let _timeout = request.remaining_time();
+///   Ok(())
+/// }
+///
+/// gateway!(wrap(
+///   Timeout::new(Duration::from_millis(200)).with_status(StatusCode::SERVICE_UNAVAILABLE),
+///   handler
+/// ));
+/// # fn main() { }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeout {
+    margin: Duration,
+    status: StatusCode,
+}
+
+impl Default for Timeout {
+    fn default() -> Self {
+        Timeout::new(Duration::from_millis(500))
+    }
+}
+
+impl Timeout {
+    /// Return new timeout middleware answering requests `margin` before the invocation times out
+    pub fn new(margin: Duration) -> Self {
+        Timeout {
+            margin,
+            status: StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    /// Set the status of responses to requests not answered in time. Defaults to
+    /// `504 Gateway Timeout`
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    fn timed_out(&self, request_id: String) -> Response<Body> {
+        let problem =
+            Problem::new(self.status).with_detail("the request could not be completed in time");
+        if request_id.is_empty() {
+            problem.into_response()
+        } else {
+            problem.with_instance(request_id).into_response()
+        }
+    }
+}
+
+impl Middleware for Timeout {
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        let deadline = match request.deadline() {
+            Some(deadline) => deadline.checked_sub(self.margin).unwrap_or(deadline),
+            _ => return next(request),
+        };
+        let request_id = request.request_context().request_id;
+        if Instant::now() >= deadline {
+            return Ok(self.timed_out(request_id));
+        }
+        match next(request.with_deadline(deadline)) {
+            Err(ref err) if err.downcast_ref::<DeadlineExceeded>().is_some() => {
+                Ok(self.timed_out(request_id))
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executor::blocking;
+    use middleware::wrap;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::thread;
+    use LambdaContext;
+
+    /// A future which never completes
+    struct Pending;
+
+    impl Future for Pending {
+        type Output = Result<()>;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+            Poll::Pending
+        }
+    }
+
+    fn request(remaining: Duration) -> Request {
+        Request::new(Body::Empty).with_deadline(Instant::now() + remaining)
+    }
+
+    #[test]
+    fn brings_deadlines_forward() {
+        let response = wrap(
+            Timeout::new(Duration::from_secs(1)),
+            |request: Request, _: ()| {
+                Ok(request
+                    .remaining_time()
+                    .map_or(false, |remaining| remaining <= Duration::from_secs(1))
+                    .to_string())
+            },
+        )(request(Duration::from_secs(2)), ())
+        .expect("handler failed");
+        assert_eq!(response.body(), &Body::from("true"));
+    }
+
+    #[test]
+    fn answers_requests_past_their_deadline() {
+        let response = wrap(Timeout::new(Duration::from_secs(1)), |_: Request, _: ()| {
+            assert!(false, "handler invoked past deadline");
+            Ok(())
+        })(request(Duration::from_millis(500)), ())
+        .expect("handler failed");
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[test]
+    fn cancels_asynchronous_handlers() {
+        let response = wrap(
+            Timeout::new(Duration::from_secs(1)).with_status(StatusCode::SERVICE_UNAVAILABLE),
+            blocking(|_, _| Pending),
+        )(
+            request(Duration::from_millis(1050)),
+            LambdaContext::default(),
+        )
+        .expect("handler failed");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn passes_through_other_errors() {
+        let err = wrap(Timeout::new(Duration::from_secs(1)), |_: Request, _: ()| {
+            thread::sleep(Duration::from_millis(20));
+            Err::<(), _>("invalid order".into())
+        })(request(Duration::from_millis(1010)), ())
+        .expect_err("error was answered");
+        assert_eq!(err.to_string(), "invalid order");
+    }
+
+    #[test]
+    fn passes_through_late_synchronous_responses() {
+        let response = wrap(Timeout::new(Duration::from_secs(1)), |_: Request, _: ()| {
+            thread::sleep(Duration::from_millis(20));
+            Ok("late")
+        })(request(Duration::from_millis(1010)), ())
+        .expect("handler failed");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn passes_through_requests_without_deadlines() {
+        let response =
+            wrap(Timeout::default(), |_: Request, _: ()| Ok("ok"))(Request::new(Body::Empty), ())
+                .expect("handler failed");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
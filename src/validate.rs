@@ -0,0 +1,481 @@
+//! Validation of deserialized payloads
+//!
+//! Types implementing [Validate](trait.Validate.html) check their own constraints, reporting
+//! each violation along with the path of the invalid field. Payloads may then be deserialized and validated
+//! in one step with [RequestExt#validated_payload](../trait.RequestExt.html#tymethod.validated_payload).
+//!
+//! The `Validate` trait may be derived for structs with named fields, declaring constraints with
+//! `#[validate(...)]` field attributes
+//!
+//! * `length(min = 1, max = 10)` - the number of characters of a string or items of a collection
+//! * `range(min = 0, max = 100)` - the bounds of a number
+//! * `email` - a string that looks like an email address
+//! * `nested` - a field whose type itself implements `Validate`
+//! * `custom = "path::to::function"` - a function of type `fn(&T) -> Result<(), String>`
+//!
+//! Constraints on `Option` fields only apply when a value is present. Field paths
+//! honor serde's `rename` and `rename_all` attributes.
+//!
+//! ```rust
+//! #[macro_use] extern crate lando;
+//! #[macro_use] extern crate serde_derive;
+//!
+//! use lando::{IntoResponse, RequestExt, Problem};
+//!
+//! #[derive(Deserialize, Validate)]
+//! struct Item {
+//!   #[validate(range(min = 0.01))]
+//!   price: f64
+//! }
+//!
+//! #[derive(Deserialize, Validate)]
+//! struct Order {
+//!   #[validate(length(min = 1, max = 100))]
+//!   name: String,
+//!   #[validate(email)]
+//!   contact: Option<String>,
+//!   #[validate(length(min = 1), nested)]
+//!   items: Vec<Item>
+//! }
+//!
+//! gateway!(|request, _| {
+//!   Ok(match request.validated_payload::<Order>() {
+//!     Ok(Some(order)) => format!("ordered {} items", order.items.len()).into_response(),
+//!     Ok(None) => lando::response::bad_request("an order is required"),
+//!     // invalid fields are listed by path, i.e. `items[3].price`, in a `422` problem response
+//!     Err(err) => Problem::from(err).with_request(&request).into_response()
+//!   })
+//! });
+//! # fn main() { }
+//! ```
+
+// Std
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::fmt;
+
+// Third Party
+use http::StatusCode;
+use serde_json;
+
+// Ours
+use problem::Problem;
+use {Body, IntoResponse, Response};
+
+/// A type which can validate its own constraints
+pub trait Validate {
+    /// Return `Ok(())` when valid or the collection of invalid fields otherwise
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+impl<T> Validate for Option<T>
+where
+    T: Validate,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Some(value) => value.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T> Validate for Vec<T>
+where
+    T: Validate,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.as_slice().validate()
+    }
+}
+
+impl<T> Validate for [T]
+where
+    T: Validate,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (index, item) in self.iter().enumerate() {
+            errors.merge(&format!("[{}]", index), item.validate());
+        }
+        errors.into_result()
+    }
+}
+
+impl<T> Validate for &T
+where
+    T: Validate + ?Sized,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        (*self).validate()
+    }
+}
+
+impl<T> Validate for Box<T>
+where
+    T: Validate + ?Sized,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.as_ref().validate()
+    }
+}
+
+/// A single constraint violation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// The path to the invalid field, i.e. `items[3].price`
+    pub path: String,
+    /// A machine readable name of the violated constraint, i.e. `length`
+    pub code: String,
+    /// A human readable description of the violation
+    pub message: String,
+}
+
+/// A collection of constraint violations
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationErrors(Vec<FieldError>);
+
+impl ValidationErrors {
+    /// Return a new, empty, collection of errors
+    pub fn new() -> Self {
+        ValidationErrors::default()
+    }
+
+    /// Add a violation for the field at `path`
+    pub fn add<P, C, M>(&mut self, path: P, code: C, message: M)
+    where
+        P: Into<String>,
+        C: Into<String>,
+        M: Into<String>,
+    {
+        self.0.push(FieldError {
+            path: path.into(),
+            code: code.into(),
+            message: message.into(),
+        })
+    }
+
+    /// Merge the errors of a nested validation, prefixing their paths with `prefix`
+    pub fn merge(&mut self, prefix: &str, result: Result<(), ValidationErrors>) {
+        if let Err(ValidationErrors(nested)) = result {
+            for mut error in nested {
+                error.path = if error.path.is_empty() {
+                    prefix.to_string()
+                } else if error.path.starts_with('[') || prefix.is_empty() {
+                    format!("{}{}", prefix, error.path)
+                } else {
+                    format!("{}.{}", prefix, error.path)
+                };
+                self.0.push(error);
+            }
+        }
+    }
+
+    /// Return true if there are no errors
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return an iterator over errors
+    pub fn iter(&self) -> ::std::slice::Iter<'_, FieldError> {
+        self.0.iter()
+    }
+
+    /// Return `Ok(())` if there are no errors, or `Err(self)` otherwise
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid fields: ")?;
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", error.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for ValidationErrors {}
+
+impl<'a> From<&'a ValidationErrors> for Problem {
+    fn from(errors: &'a ValidationErrors) -> Self {
+        Problem::new(StatusCode::UNPROCESSABLE_ENTITY)
+            .with_detail(errors.to_string())
+            .with_extension(
+                "errors",
+                serde_json::to_value(&errors.0).unwrap_or_default(),
+            )
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response<Body> {
+        Problem::from(&self).into_response()
+    }
+}
+
+/// Types with a measurable length
+pub trait HasLength {
+    /// Return the length of this value
+    fn length(&self) -> usize;
+}
+
+impl HasLength for String {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl HasLength for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl<T> HasLength for &T
+where
+    T: HasLength + ?Sized,
+{
+    fn length(&self) -> usize {
+        (*self).length()
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, S> HasLength for HashMap<K, V, S>
+where
+    K: ::std::hash::Hash + Eq,
+    S: ::std::hash::BuildHasher,
+{
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> HasLength for BTreeMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Numeric types whose bounds may be validated
+pub trait Numeric {
+    /// Return this value as an `f64` for comparison
+    fn as_f64(&self) -> f64;
+}
+
+macro_rules! numeric {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn as_f64(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    };
+}
+
+numeric!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Validate that a value's length is within `min` and `max`, inclusive
+pub fn length<T>(
+    errors: &mut ValidationErrors,
+    path: &str,
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+) where
+    T: HasLength + ?Sized,
+{
+    let len = value.length();
+    match (min, max) {
+        (Some(min), _) if len < min => {
+            errors.add(path, "length", format!("length must be at least {}", min))
+        }
+        (_, Some(max)) if len > max => {
+            errors.add(path, "length", format!("length must be at most {}", max))
+        }
+        _ => (),
+    }
+}
+
+/// Validate that a number is within `min` and `max`, inclusive
+pub fn range<T>(
+    errors: &mut ValidationErrors,
+    path: &str,
+    value: &T,
+    min: Option<f64>,
+    max: Option<f64>,
+) where
+    T: Numeric,
+{
+    let number = value.as_f64();
+    match (min, max) {
+        _ if number.is_nan() => errors.add(path, "range", "must be a number"),
+        (Some(min), _) if number < min => {
+            errors.add(path, "range", format!("must be at least {}", min))
+        }
+        (_, Some(max)) if number > max => {
+            errors.add(path, "range", format!("must be at most {}", max))
+        }
+        _ => (),
+    }
+}
+
+/// Validate that a string looks like an email address
+pub fn email<T>(errors: &mut ValidationErrors, path: &str, value: &T)
+where
+    T: AsRef<str> + ?Sized,
+{
+    let address = value.as_ref();
+    let mut parts = address.splitn(2, '@');
+    let valid = match (parts.next(), parts.next()) {
+        (Some(local), Some(domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !address.chars().any(char::is_whitespace)
+        }
+        _ => false,
+    };
+    if !valid {
+        errors.add(path, "email", "must be an email address");
+    }
+}
+
+/// Validate a nested value, prefixing its errors with `path`
+pub fn nested<T>(errors: &mut ValidationErrors, path: &str, value: &T)
+where
+    T: Validate + ?Sized,
+{
+    errors.merge(path, value.validate())
+}
+
+/// Validate a value with a custom function
+pub fn custom<T, F>(errors: &mut ValidationErrors, path: &str, value: &T, f: F)
+where
+    T: ?Sized,
+    F: FnOnce(&T) -> Result<(), String>,
+{
+    if let Err(message) = f(value) {
+        errors.add(path, "custom", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Item {
+        price: f64,
+    }
+
+    impl Validate for Item {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            range(&mut errors, "price", &self.price, Some(0.0), None);
+            errors.into_result()
+        }
+    }
+
+    struct Order {
+        name: String,
+        contact: Option<String>,
+        items: Vec<Item>,
+    }
+
+    impl Validate for Order {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            length(&mut errors, "name", &self.name, Some(1), Some(3));
+            if let Some(ref contact) = self.contact {
+                email(&mut errors, "contact", contact);
+            }
+            nested(&mut errors, "items", &self.items);
+            errors.into_result()
+        }
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Order {
+                name: "abc".into(),
+                contact: None,
+                items: vec![Item { price: 1.0 }],
+            }
+            .validate(),
+            Ok(())
+        )
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let errors = Order {
+            name: "abcd".into(),
+            contact: Some("nope".into()),
+            items: vec![Item { price: 1.0 }, Item { price: -1.0 }],
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+            vec!["name", "contact", "items[1].price"]
+        );
+    }
+
+    #[test]
+    fn validates_emails() {
+        for (address, valid) in &[
+            ("foo@bar.com", true),
+            ("foo", false),
+            ("@bar.com", false),
+            ("foo@bar", false),
+            ("foo bar@baz.com", false),
+        ] {
+            let mut errors = ValidationErrors::new();
+            email(&mut errors, "email", address);
+            assert_eq!(errors.is_empty(), *valid, "{}", address);
+        }
+    }
+
+    #[test]
+    fn renders_unprocessable_problems() {
+        let mut errors = ValidationErrors::new();
+        errors.add("name", "length", "length must be at least 1");
+        let problem = Problem::from(&errors);
+        assert_eq!(problem.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            problem.extension("errors"),
+            Some(&json!([{
+                "path": "name",
+                "code": "length",
+                "message": "length must be at least 1"
+            }]))
+        );
+    }
+}
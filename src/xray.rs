@@ -0,0 +1,466 @@
+//! AWS X-Ray trace propagation
+//!
+//! Requests traced by [X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/aws-xray.html)
+//! carry a trace header, `X-Amzn-Trace-Id`, of the form
+//! `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+//! API gateway forwards it to functions and Lambda exposes the invocation's trace header,
+//! whose parent is the function's own segment, through the `_X_AMZN_TRACE_ID` environment
+//! variable, which takes precedence when both are present.
+//!
+//! A request's [TraceContext](struct.TraceContext.html) is available through
+//! [RequestExt#trace_context](../trait.RequestExt.html#tymethod.trace_context) and may be
+//! propagated to calls to downstream services so that they join the same trace.
+//!
+//! ```rust
+//! # #[macro_use] extern crate lando;
+//! use lando::http::Request as HttpRequest;
+//! use lando::middleware::wrap;
+//! use lando::xray::Tracing;
+//! use lando::{IntoResponse, LambdaContext, Request, RequestExt, Result};
+//!
+//! fn handler(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+//!   let mut downstream = HttpRequest::get("https://example.com/users").body(())?;
+//!   if let Some(trace) = request.trace_context() {
+//!     trace.propagate(&mut downstream);
+//!   }
+//!   Ok(())
+//! }
+//!
+//! gateway!(wrap(Tracing::new().with_name("users"), handler));
+//! # fn main() { }
+//! ```
+//!
+//! [Tracing](struct.Tracing.html) middleware additionally records the time spent handling
+//! sampled requests as a subsegment sent to the X-Ray daemon.
+
+// Std
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::UdpSocket;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Third Party
+use http::header::HeaderValue;
+use http::Request as HttpRequest;
+use serde_json::{json, Value};
+
+// Ours
+use ext::RequestExt;
+use middleware::Middleware;
+use {Body, Request, Response, Result};
+
+/// The name of the http header carrying trace contexts
+pub const TRACE_HEADER: &str = "x-amzn-trace-id";
+
+/// The environment variable Lambda exposes the invocation's trace header through
+pub const TRACE_ENV: &str = "_X_AMZN_TRACE_ID";
+
+/// The environment variable Lambda exposes the X-Ray daemon's address through
+pub const DAEMON_ADDRESS_ENV: &str = "AWS_XRAY_DAEMON_ADDRESS";
+
+const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:2000";
+
+/// Errors parsing trace headers
+#[derive(Debug, Fail, PartialEq)]
+#[fail(display = "invalid trace header {:?}: {}", header, reason)]
+pub struct TraceHeaderError {
+    header: String,
+    reason: &'static str,
+}
+
+/// An X-Ray trace context, identifying a trace and the segment within it
+/// which made a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceContext {
+    root: String,
+    parent: Option<String>,
+    sampled: Option<bool>,
+}
+
+impl TraceContext {
+    /// Return a new trace context for trace `root`
+    pub fn new<R>(root: R) -> Self
+    where
+        R: Into<String>,
+    {
+        TraceContext {
+            root: root.into(),
+            parent: None,
+            sampled: None,
+        }
+    }
+
+    /// Return the trace id, i.e. `1-5759e988-bd862e3fe1be46a994272793`
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Return the id of the segment which made the request, if any
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_ref().map(String::as_str)
+    }
+
+    /// Return the upstream sampling decision. `None` when the decision was deferred
+    pub fn sampled(&self) -> Option<bool> {
+        self.sampled
+    }
+
+    /// Return a trace context with the given parent segment id. Pass the id of the segment
+    /// making a downstream call so the call is attributed to it
+    pub fn with_parent<P>(mut self, parent: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.parent = Some(parent.into());
+        self
+    }
+
+    /// Return a trace context with the given sampling decision
+    pub fn with_sampled(mut self, sampled: bool) -> Self {
+        self.sampled = Some(sampled);
+        self
+    }
+
+    /// Return the trace context of the current Lambda invocation, if traced
+    pub fn from_env() -> Option<Self> {
+        env::var(TRACE_ENV)
+            .ok()
+            .and_then(|header| header.parse().ok())
+    }
+
+    /// Return this trace context as a header value
+    ///
+    /// Trace contexts built with ids which can not be represented in a trace header, i.e.
+    /// containing `;` or control characters, yield `None`
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        let header = self.to_string();
+        match header.parse::<TraceContext>() {
+            Ok(ref parsed) if parsed == self => HeaderValue::from_str(&header).ok(),
+            _ => None,
+        }
+    }
+
+    /// Add this trace context's header to an outgoing request. Return false, leaving the
+    /// request as it is, when this trace context has no [header_value](#method.header_value)
+    pub fn propagate<B>(&self, request: &mut HttpRequest<B>) -> bool {
+        match self.header_value() {
+            Some(header) => {
+                request.headers_mut().insert(TRACE_HEADER, header);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for TraceContext {
+    type Err = TraceHeaderError;
+
+    fn from_str(header: &str) -> ::std::result::Result<Self, Self::Err> {
+        let invalid = |reason| TraceHeaderError {
+            header: header.into(),
+            reason,
+        };
+        if header.bytes().any(|b| !(0x20..0x7f).contains(&b)) {
+            return Err(invalid("headers must be visible ascii"));
+        }
+        let (mut root, mut parent, mut sampled) = (None, None, None);
+        for field in header.split(';').map(str::trim).filter(|f| !f.is_empty()) {
+            let mut pair = field.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some("Root"), Some(value)) if !value.is_empty() => root = Some(value.to_owned()),
+                (Some("Parent"), Some(value)) if !value.is_empty() => {
+                    parent = Some(value.to_owned())
+                }
+                (Some("Sampled"), Some("1")) => sampled = Some(true),
+                (Some("Sampled"), Some("0")) => sampled = Some(false),
+                (Some("Sampled"), Some("?")) => sampled = None,
+                (Some("Sampled"), _) => return Err(invalid("Sampled must be 1, 0 or ?")),
+                (Some(_), Some(_)) => (),
+                _ => return Err(invalid("expected Key=Value fields")),
+            }
+        }
+        Ok(TraceContext {
+            root: root.ok_or_else(|| invalid("missing Root"))?,
+            parent,
+            sampled,
+        })
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Root={}", self.root)?;
+        if let Some(parent) = &self.parent {
+            write!(f, ";Parent={}", parent)?;
+        }
+        match self.sampled {
+            Some(true) => write!(f, ";Sampled=1"),
+            Some(false) => write!(f, ";Sampled=0"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Return a new random 64 bit segment id, as 16 hex digits
+fn segment_id() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    // std's randomly keyed hasher is random enough to keep ids unique within a trace
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+fn epoch_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| {
+            elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0
+        })
+        .unwrap_or_default()
+}
+
+/// Middleware recording the time spent handling sampled requests as X-Ray subsegments
+///
+/// Handlers see a trace context whose parent is the subsegment, so that propagated calls
+/// are attributed to it. Subsegments are sent as UDP datagrams to the daemon address
+/// Lambda exposes through `AWS_XRAY_DAEMON_ADDRESS`, defaulting to `127.0.0.1:2000`.
+/// Failures to send are ignored, as the daemon may not be running outside of Lambda.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracing {
+    name: String,
+    daemon: Option<String>,
+}
+
+impl Default for Tracing {
+    fn default() -> Self {
+        Tracing {
+            name: env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or_else(|_| "handler".into()),
+            daemon: None,
+        }
+    }
+}
+
+impl Tracing {
+    /// Return new tracing middleware naming subsegments after the function
+    pub fn new() -> Self {
+        Tracing::default()
+    }
+
+    /// Set the name of recorded subsegments
+    pub fn with_name<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the address of the daemon subsegments are sent to, i.e. `127.0.0.1:2000`
+    pub fn with_daemon<A>(mut self, address: A) -> Self
+    where
+        A: Into<String>,
+    {
+        self.daemon = Some(address.into());
+        self
+    }
+
+    fn daemon(&self) -> String {
+        self.daemon
+            .clone()
+            .or_else(|| env::var(DAEMON_ADDRESS_ENV).ok())
+            .unwrap_or_else(|| DEFAULT_DAEMON_ADDRESS.into())
+    }
+
+    /// Return a subsegment document for a handled request
+    fn subsegment(
+        &self,
+        id: String,
+        trace: &TraceContext,
+        start: SystemTime,
+        end: SystemTime,
+        result: &Result<Response<Body>>,
+    ) -> Value {
+        let mut document = json!({
+            "name": self.name,
+            "id": id,
+            "trace_id": trace.root(),
+            "parent_id": trace.parent(),
+            "type": "subsegment",
+            "start_time": epoch_seconds(start),
+            "end_time": epoch_seconds(end),
+        });
+        let status = match result {
+            Ok(response) => response.status().as_u16(),
+            Err(err) => {
+                document["cause"] = json!({ "exceptions": [{ "message": err.to_string() }] });
+                500
+            }
+        };
+        document["http"] = json!({ "response": { "status": status } });
+        if status >= 500 {
+            document["fault"] = true.into();
+        } else if status >= 400 {
+            document["error"] = true.into();
+        }
+        document
+    }
+
+    fn send(&self, document: &Value) {
+        let datagram = format!("{{\"format\": \"json\", \"version\": 1}}\n{}", document);
+        // tracing should never fail a request
+        if let Ok(socket) = UdpSocket::bind("0.0.0.0:0") {
+            let _ = socket.send_to(datagram.as_bytes(), self.daemon().as_str());
+        }
+    }
+}
+
+impl Middleware for Tracing {
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        let trace = match request.trace_context() {
+            // subsegments must belong to a sampled segment
+            Some(ref trace) if trace.sampled() == Some(true) && trace.parent().is_some() => {
+                trace.clone()
+            }
+            _ => return next(request),
+        };
+        let id = segment_id();
+        let start = SystemTime::now();
+        let result = next(request.with_trace_context(trace.clone().with_parent(id.as_str())));
+        self.send(&self.subsegment(id, &trace, start, SystemTime::now(), &result));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use middleware::wrap;
+    use std::time::Duration;
+
+    const HEADER: &str =
+        "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1";
+
+    fn traced(header: &str) -> Request {
+        let mut request = Request::new(Body::Empty);
+        request
+            .headers_mut()
+            .insert(TRACE_HEADER, HeaderValue::from_str(header).unwrap());
+        request
+    }
+
+    #[test]
+    fn parses_trace_headers() {
+        let trace: TraceContext = HEADER.parse().expect("invalid header");
+        assert_eq!(trace.root(), "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(trace.parent(), Some("53995c3f42cd8ad8"));
+        assert_eq!(trace.sampled(), Some(true));
+        assert_eq!(trace.to_string(), HEADER);
+
+        let trace: TraceContext = "Root=1-abc;Sampled=?;Lineage=a:1".parse().unwrap();
+        assert_eq!(trace, TraceContext::new("1-abc"));
+        assert_eq!(trace.to_string(), "Root=1-abc");
+
+        for header in &[
+            "",
+            "Parent=53995c3f42cd8ad8",
+            "Root=1-abc;Sampled=yes",
+            "Root",
+        ] {
+            assert!(
+                header.parse::<TraceContext>().is_err(),
+                "accepted {}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn propagates_trace_contexts() {
+        let trace = traced(HEADER)
+            .trace_context()
+            .expect("missing trace context")
+            .with_parent("0123456789abcdef");
+        let mut downstream = HttpRequest::new(());
+        assert!(trace.propagate(&mut downstream));
+        assert_eq!(
+            downstream.headers()[TRACE_HEADER],
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=0123456789abcdef;Sampled=1"
+        );
+    }
+
+    #[test]
+    fn does_not_propagate_invalid_trace_contexts() {
+        for trace in &[
+            TraceContext::new("1-abc").with_parent("a\nb"),
+            TraceContext::new("1-abc;Parent=injected"),
+            TraceContext::new("1-abc").with_parent(""),
+            TraceContext::new("1-✓"),
+        ] {
+            assert_eq!(trace.header_value(), None, "accepted {:?}", trace);
+            let mut downstream = HttpRequest::new(());
+            assert!(!trace.propagate(&mut downstream));
+            assert!(downstream.headers().is_empty());
+        }
+    }
+
+    #[test]
+    fn generates_segment_ids() {
+        let (a, b) = (segment_id(), segment_id());
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn emits_subsegments_to_daemon() {
+        let daemon = UdpSocket::bind("127.0.0.1:0").expect("failed to bind daemon");
+        daemon
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let tracing = Tracing::new()
+            .with_name("orders")
+            .with_daemon(daemon.local_addr().unwrap().to_string());
+        let response = wrap(tracing, |request: Request, _: ()| {
+            let trace = request.trace_context().expect("missing trace context");
+            assert_ne!(trace.parent(), Some("53995c3f42cd8ad8"));
+            Ok(trace.parent().unwrap_or_default().to_owned())
+        })(traced(HEADER), ())
+        .expect("handler failed");
+
+        let mut buf = [0; 4096];
+        let len = daemon.recv(&mut buf).expect("no subsegment received");
+        let datagram = String::from_utf8_lossy(&buf[..len]).into_owned();
+        let mut lines = datagram.splitn(2, '\n');
+        assert_eq!(lines.next(), Some("{\"format\": \"json\", \"version\": 1}"));
+        let document: Value =
+            serde_json::from_str(lines.next().unwrap()).expect("invalid subsegment");
+        assert_eq!(document["name"], "orders");
+        assert_eq!(document["type"], "subsegment");
+        assert_eq!(document["trace_id"], "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(document["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(document["http"]["response"]["status"], 200);
+        assert_eq!(
+            response.body(),
+            &Body::from(document["id"].as_str().unwrap())
+        );
+        assert!(document["end_time"].as_f64() >= document["start_time"].as_f64());
+    }
+
+    #[test]
+    fn skips_unsampled_requests() {
+        let daemon = UdpSocket::bind("127.0.0.1:0").expect("failed to bind daemon");
+        daemon
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let tracing = Tracing::new().with_daemon(daemon.local_addr().unwrap().to_string());
+        let header = "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=0";
+        wrap(tracing, |_: Request, _: ()| Ok(()))(traced(header), ()).expect("handler failed");
+        assert!(daemon.recv(&mut [0; 64]).is_err());
+    }
+}
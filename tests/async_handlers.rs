@@ -0,0 +1,102 @@
+// async fns require the 2018 edition, which Cargo.toml enables for this target only
+#[macro_use]
+extern crate lando;
+
+use lando::testing::RequestBuilder;
+use lando::{Body, IntoResponse, LambdaContext, Request, RequestExt, Result};
+
+async fn lookup(id: &str) -> Result<String> {
+    Ok(format!("user {}", id))
+}
+
+#[lando]
+async fn hello(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok("hello")
+}
+
+#[lando(route = "GET /users/{id}")]
+async fn get_user(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    let id = request
+        .path_parameters()
+        .get("id")
+        .unwrap_or_default()
+        .to_owned();
+    lookup(&id).await
+}
+
+export!(hello);
+
+#[test]
+fn attributed_async_handlers_run_to_completion() {
+    let response = __lando_handler_hello(Request::new(Body::Empty), LambdaContext::default())
+        .expect("handler failed");
+    assert_eq!(response.body(), &Body::from("hello"));
+}
+
+#[test]
+fn attributed_async_routes_await_other_futures() {
+    let request = RequestBuilder::new()
+        .uri("https://example.com/users/42")
+        .body(())
+        .expect("failed to build request");
+    let response = routes![get_user]
+        .handle(request, LambdaContext::default())
+        .expect("handler failed");
+    assert_eq!(response.body(), &Body::from("user 42"));
+}
+
+// handlers awaiting tokio's reactor and timers require the tokio feature
+#[cfg(feature = "tokio")]
+mod reactor {
+    use std::time::{Duration, Instant};
+
+    use lando::executor::blocking;
+    use lando::{Body, DeadlineExceeded, LambdaContext, Request, RequestExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time::sleep;
+
+    #[test]
+    fn blocking_handlers_await_timers() {
+        let handler = blocking(|_, _| async {
+            sleep(Duration::from_millis(10)).await;
+            Ok("slept")
+        });
+        let started = Instant::now();
+        let response =
+            handler(Request::new(Body::Empty), LambdaContext::default()).expect("handler failed");
+        assert_eq!(response, "slept");
+        assert!(started.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn blocking_handlers_await_sockets() {
+        // each invocation reuses the same runtime
+        for _ in 0..2 {
+            let handler = blocking(|_, _| async {
+                let listener = TcpListener::bind("127.0.0.1:0").await?;
+                let mut client = TcpStream::connect(listener.local_addr()?).await?;
+                let (mut server, _) = listener.accept().await?;
+                server.write_all(b"pong").await?;
+                let mut buf = [0; 4];
+                client.read_exact(&mut buf).await?;
+                Ok(buf)
+            });
+            let response = handler(Request::new(Body::Empty), LambdaContext::default())
+                .expect("handler failed");
+            assert_eq!(&response, b"pong");
+        }
+    }
+
+    #[test]
+    fn blocking_handlers_are_cancelled_while_awaiting_timers() {
+        let request =
+            Request::new(Body::Empty).with_deadline(Instant::now() + Duration::from_millis(10));
+        let handler = blocking(|_, _| async {
+            sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+        let err = handler(request, LambdaContext::default()).expect_err("handler finished");
+        assert!(err.downcast_ref::<DeadlineExceeded>().is_some());
+    }
+}
@@ -7,3 +7,5 @@ use lando::{IntoResponse, LambdaContext, Request, Result};
 pub fn example(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
     Ok("👋  well hello there. What have we here?")
 }
+
+export!(example);
@@ -0,0 +1,60 @@
+#[macro_use]
+extern crate lando;
+
+use lando::{Body, IntoResponse, LambdaContext, Limits, Request, RequestExt, Result};
+
+struct Greeting(&'static str);
+
+fn greeting() -> Result<Greeting> {
+    Ok(Greeting("hello"))
+}
+
+#[lando]
+fn users(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok("users")
+}
+
+#[lando(name = "order_handler", middleware = [Limits {
+    max_body_bytes: Some(1),
+    ..Limits::default()
+}])]
+fn orders(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok("orders")
+}
+
+#[lando(state = greeting)]
+fn greet(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok(request
+        .state::<Greeting>()
+        .map(|greeting| greeting.0)
+        .unwrap_or_default())
+}
+
+// one module initializer for every attributed function
+export!(users, orders, greet);
+
+#[test]
+fn handlers_are_exported_by_name() {
+    assert_eq!(__lando_name_users, "users");
+    assert_eq!(__lando_name_orders, "order_handler");
+}
+
+#[test]
+fn exported_handlers_apply_middleware() {
+    let response = __lando_handler_users(Request::new(Body::Empty), LambdaContext::default())
+        .expect("handler failed");
+    assert_eq!(response.body(), &Body::from("users"));
+    let response = __lando_handler_orders(
+        Request::new(Body::from("too large")),
+        LambdaContext::default(),
+    )
+    .expect("handler failed");
+    assert_eq!(response.status(), 413);
+}
+
+#[test]
+fn exported_handlers_receive_state() {
+    let response = __lando_handler_greet(Request::new(Body::Empty), LambdaContext::default())
+        .expect("handler failed");
+    assert_eq!(response.body(), &Body::from("hello"));
+}
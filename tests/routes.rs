@@ -0,0 +1,71 @@
+#[macro_use]
+extern crate lando;
+
+use lando::http::header::HeaderValue;
+use lando::http::StatusCode;
+use lando::middleware::Middleware;
+use lando::testing::RequestBuilder;
+use lando::{Body, IntoResponse, LambdaContext, Request, RequestExt, Response, Result};
+
+struct Tag(&'static str);
+
+impl Middleware for Tag {
+    fn handle<N>(&self, request: Request, next: N) -> Result<Response<Body>>
+    where
+        N: FnOnce(Request) -> Result<Response<Body>>,
+    {
+        let mut response = next(request)?;
+        response
+            .headers_mut()
+            .append("x-tag", HeaderValue::from_static(self.0));
+        Ok(response)
+    }
+}
+
+#[lando(route = "GET /users/{id}", middleware = [Tag("outer"), Tag("inner")])]
+fn get_user(request: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok(format!(
+        "user {}",
+        request.path_parameters().get("id").unwrap_or_default()
+    ))
+}
+
+#[lando(route = "POST /users")]
+pub fn create_user(_: Request, _: LambdaContext) -> Result<impl IntoResponse> {
+    Ok(lando::response::created("/users/1"))
+}
+
+fn dispatch(method: &str, uri: &str) -> Response<Body> {
+    routes![get_user, create_user]
+        .handle(
+            RequestBuilder::new()
+                .method(method)
+                .uri(uri)
+                .body(())
+                .expect("failed to build request"),
+            LambdaContext::default(),
+        )
+        .expect("handler failed")
+}
+
+#[test]
+fn routes_attributed_handlers() {
+    let response = dispatch("GET", "https://example.com/users/42");
+    assert_eq!(response.body(), &Body::from("user 42"));
+    assert_eq!(
+        response
+            .headers()
+            .get_all("x-tag")
+            .iter()
+            .collect::<Vec<_>>(),
+        vec!["inner", "outer"]
+    );
+    assert_eq!(
+        dispatch("POST", "https://example.com/users").status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        dispatch("DELETE", "https://example.com/users").status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
@@ -0,0 +1,31 @@
+extern crate lando;
+
+use std::env;
+
+use lando::xray::TRACE_ENV;
+use lando::{Body, RequestExt};
+
+// the only test in this target, as it sets process wide environment variables
+#[test]
+fn invocation_traces_take_precedence_over_headers() {
+    let request = lando::http::Request::builder()
+        .header(
+            "X-Amzn-Trace-Id",
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+        )
+        .body(Body::Empty)
+        .expect("failed to build request");
+
+    env::set_var(
+        TRACE_ENV,
+        "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=8a3c60f7d188f8fa;Sampled=1",
+    );
+    let trace = request.trace_context();
+    env::remove_var(TRACE_ENV);
+    let trace = trace.expect("missing trace context");
+    assert_eq!(trace.root(), "1-5759e988-bd862e3fe1be46a994272793");
+    assert_eq!(trace.parent(), Some("8a3c60f7d188f8fa"));
+
+    let trace = request.trace_context().expect("missing trace context");
+    assert_eq!(trace.parent(), Some("53995c3f42cd8ad8"));
+}
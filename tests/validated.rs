@@ -0,0 +1,77 @@
+extern crate lando;
+#[macro_use]
+extern crate serde_derive;
+
+use lando::{Validate, ValidationErrors};
+
+fn not_admin(name: &String) -> Result<(), String> {
+    if name == "admin" {
+        Err("reserved name".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Validate)]
+struct Item {
+    #[validate(range(min = 0.01))]
+    price: f64,
+    #[validate(range(min = "-10", max = 10))]
+    offset: i32,
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+struct Order {
+    #[validate(length(min = 1, max = 8), custom = "not_admin")]
+    user_name: String,
+    #[validate(email)]
+    contact: Option<String>,
+    #[serde(rename = "lineItems")]
+    #[validate(length(min = 1), nested)]
+    items: Vec<Item>,
+}
+
+fn paths(errors: ValidationErrors) -> Vec<String> {
+    errors.iter().map(|err| err.path.clone()).collect()
+}
+
+#[test]
+fn valid_payloads_pass() {
+    let order = Order {
+        user_name: "bob".into(),
+        contact: None,
+        items: vec![Item {
+            price: 1.0,
+            offset: -10,
+        }],
+    };
+    assert!(order.validate().is_ok());
+}
+
+#[test]
+fn invalid_fields_are_reported_by_path() {
+    let order = Order {
+        user_name: "admin".into(),
+        contact: Some("nope".into()),
+        items: vec![
+            Item {
+                price: 1.0,
+                offset: 0,
+            },
+            Item {
+                price: 0.0,
+                offset: 11,
+            },
+        ],
+    };
+    assert_eq!(
+        paths(order.validate().unwrap_err()),
+        vec![
+            "userName",
+            "contact",
+            "lineItems[1].price",
+            "lineItems[1].offset",
+        ]
+    );
+}